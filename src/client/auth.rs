@@ -0,0 +1,98 @@
+use crate::client::CathbadClientError;
+use futures_util::future::BoxFuture;
+use reqwest::header::{HeaderName, HeaderValue};
+use serde::{Deserialize, Deserializer};
+use std::convert::Infallible;
+use std::fmt::{Debug, Formatter};
+use std::str::FromStr;
+
+// Credentials are wrapped so they never end up in Debug output or logs
+#[derive(Clone, Eq, PartialEq)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn new(value: impl Into<String>) -> Self {
+        Self(value.into())
+    }
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Debug for Secret {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str("<redacted>")
+    }
+}
+
+impl FromStr for Secret {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self::new(s))
+    }
+}
+
+impl<'de> Deserialize<'de> for Secret {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(Self)
+    }
+}
+
+// Parsed from "Name: value", values are marked sensitive so reqwest won't print them either
+#[derive(Clone)]
+pub struct ExtraHeader {
+    pub name: HeaderName,
+    pub value: HeaderValue,
+}
+
+impl Debug for ExtraHeader {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: <redacted>", self.name)
+    }
+}
+
+impl FromStr for ExtraHeader {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, value) = s
+            .split_once(':')
+            .ok_or_else(|| format!("expected \"Name: value\", got \"{s}\""))?;
+        let name = HeaderName::from_str(name.trim()).map_err(|e| e.to_string())?;
+        let mut value = HeaderValue::from_str(value.trim()).map_err(|e| e.to_string())?;
+        value.set_sensitive(true);
+        Ok(Self { name, value })
+    }
+}
+
+impl<'de> Deserialize<'de> for ExtraHeader {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+// For tokens that expire, e.g. OIDC access tokens. The client asks for a token on every request
+// and calls refresh once when the broker answers 401, then retries the request with the new token.
+pub trait TokenProvider: Send + Sync {
+    fn token(&self) -> BoxFuture<'_, Result<Secret, CathbadClientError>>;
+    fn refresh(&self) -> BoxFuture<'_, Result<(), CathbadClientError>>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extra_header_parsing() {
+        let header: ExtraHeader = "X-Druid-Tenant: analytics".parse().unwrap();
+        assert_eq!(header.name, "x-druid-tenant");
+        assert_eq!(header.value, "analytics");
+        assert!(header.value.is_sensitive());
+
+        assert!("no separator".parse::<ExtraHeader>().is_err());
+        assert!("Bad Name: value".parse::<ExtraHeader>().is_err());
+    }
+}
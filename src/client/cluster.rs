@@ -0,0 +1,378 @@
+use crate::client::{CathbadClientError, DruidEndpoint};
+use futures_util::future::join_all;
+use reqwest::Client;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RoutingStrategy {
+    #[default]
+    RoundRobin,
+    LeastInFlight,
+}
+
+#[derive(Debug, Clone)]
+pub struct BalancerPolicy {
+    pub strategy: RoutingStrategy,
+    // Consecutive failures before a node is taken out of rotation
+    pub failure_threshold: u32,
+    // How long an open circuit keeps the node out before a trial request is let through
+    pub open_duration: Duration,
+    // GET /status/health on every node, None disables probing
+    pub health_check_interval: Option<Duration>,
+}
+
+impl Default for BalancerPolicy {
+    fn default() -> Self {
+        Self {
+            strategy: RoutingStrategy::RoundRobin,
+            failure_threshold: 3,
+            open_duration: Duration::from_secs(30),
+            health_check_interval: Some(Duration::from_secs(10)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NodeStatus {
+    pub endpoint: DruidEndpoint,
+    pub healthy: bool,
+    pub circuit_open: bool,
+    pub in_flight: usize,
+}
+
+#[derive(Debug, Default)]
+struct NodeState {
+    unhealthy: bool,
+    failures: u32,
+    open_until: Option<Instant>,
+}
+
+#[derive(Debug)]
+pub(crate) struct Node {
+    pub(crate) endpoint: DruidEndpoint,
+    in_flight: AtomicUsize,
+    state: Mutex<NodeState>,
+}
+
+impl Node {
+    fn available(&self, now: Instant) -> bool {
+        let state = self.state.lock().unwrap();
+        !state.unhealthy && state.open_until.is_none_or(|until| until <= now)
+    }
+
+    pub(crate) fn record_success(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.failures = 0;
+        state.open_until = None;
+    }
+
+    // Once open, a failed trial request re-opens the circuit straight away
+    pub(crate) fn record_failure(&self, policy: &BalancerPolicy) {
+        let mut state = self.state.lock().unwrap();
+        state.failures += 1;
+        if state.failures >= policy.failure_threshold {
+            state.open_until = Some(Instant::now() + policy.open_duration);
+        }
+    }
+
+    pub(crate) fn begin(&self) -> InFlight<'_> {
+        self.in_flight.fetch_add(1, Ordering::SeqCst);
+        InFlight(self)
+    }
+
+    fn status(&self) -> NodeStatus {
+        let state = self.state.lock().unwrap();
+        NodeStatus {
+            endpoint: self.endpoint.clone(),
+            healthy: !state.unhealthy,
+            circuit_open: state.open_until.is_some_and(|until| until > Instant::now()),
+            in_flight: self.in_flight.load(Ordering::SeqCst),
+        }
+    }
+}
+
+pub(crate) struct InFlight<'a>(&'a Node);
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        self.0.in_flight.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+// The brokers or routers a client spreads its queries over
+#[derive(Debug)]
+pub(crate) struct Cluster {
+    nodes: Vec<Node>,
+    pub(crate) policy: BalancerPolicy,
+    next: AtomicUsize,
+    probing: AtomicBool,
+}
+
+impl Cluster {
+    pub(crate) fn new(endpoints: Vec<DruidEndpoint>, policy: BalancerPolicy) -> Self {
+        Self {
+            nodes: endpoints
+                .into_iter()
+                .map(|endpoint| Node {
+                    endpoint,
+                    in_flight: AtomicUsize::new(0),
+                    state: Mutex::new(NodeState::default()),
+                })
+                .collect(),
+            policy,
+            next: AtomicUsize::new(0),
+            probing: AtomicBool::new(false),
+        }
+    }
+
+    pub(crate) fn nodes(&self) -> &[Node] {
+        &self.nodes
+    }
+
+    // Picks among the available nodes this request hasn't tried yet. When every node is out of
+    // rotation it falls back to the rest of them, a request is never failed without being sent.
+    pub(crate) fn select(&self, tried: &[usize]) -> usize {
+        let now = Instant::now();
+        let untried: Vec<usize> = (0..self.nodes.len())
+            .filter(|index| !tried.contains(index))
+            .collect();
+        let mut candidates: Vec<usize> = untried
+            .iter()
+            .copied()
+            .filter(|index| self.nodes[*index].available(now))
+            .collect();
+        if candidates.is_empty() {
+            candidates = untried;
+        }
+        if candidates.is_empty() {
+            candidates = (0..self.nodes.len()).collect();
+        }
+
+        let offset = self.next.fetch_add(1, Ordering::Relaxed) % candidates.len();
+        candidates.rotate_left(offset);
+        match self.policy.strategy {
+            RoutingStrategy::RoundRobin => candidates[0],
+            RoutingStrategy::LeastInFlight => candidates
+                .into_iter()
+                .min_by_key(|index| self.nodes[*index].in_flight.load(Ordering::SeqCst))
+                .unwrap(),
+        }
+    }
+
+    pub(crate) fn status(&self) -> Vec<NodeStatus> {
+        self.nodes.iter().map(Node::status).collect()
+    }
+
+    // Started by the first request, there may be no runtime when the client is built. The task
+    // ends once the last client sharing the cluster is dropped.
+    pub(crate) fn start_probing(self: &Arc<Self>, client: &Client) {
+        let Some(interval) = self.policy.health_check_interval else {
+            return;
+        };
+        if self.nodes.len() < 2 || self.probing.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            self.probing.store(false, Ordering::SeqCst);
+            return;
+        };
+
+        let cluster = Arc::downgrade(self);
+        let client = client.clone();
+        runtime.spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                let Some(cluster) = cluster.upgrade() else {
+                    break;
+                };
+                let probes = cluster
+                    .nodes
+                    .iter()
+                    .map(|node| probe(&client, &node.endpoint, interval));
+                for (node, healthy) in cluster.nodes.iter().zip(join_all(probes).await) {
+                    node.state.lock().unwrap().unhealthy = !healthy;
+                }
+            }
+        });
+    }
+}
+
+async fn probe(client: &Client, endpoint: &DruidEndpoint, timeout: Duration) -> bool {
+    match client.get(endpoint.health()).timeout(timeout).send().await {
+        Ok(resp) if resp.status().is_success() => {
+            resp.text().await.is_ok_and(|body| body.trim() == "true")
+        }
+        _ => false,
+    }
+}
+
+impl CathbadClientError {
+    // A broker that answers with a Druid error body is up, only transport failures and 5xx from
+    // whatever sits in front of it count against the node. A timeout is a slow query rather than
+    // a dead node, failing over would run it again elsewhere.
+    pub fn is_node_failure(&self) -> bool {
+        match self {
+            CathbadClientError::Reqwest { reqwest_error } => {
+                !reqwest_error.is_timeout()
+                    && (reqwest_error.is_connect() || reqwest_error.is_request())
+            }
+            CathbadClientError::DruidErrorUnmarshal { status, .. } => status.is_server_error(),
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::{CathbadClient, CathbadClientConfig, DruidClient, RetryPolicy};
+    use crate::query::{Context, DataSource, DatasourceMetadataQuery, SqlQuery, SqlResponse};
+    use crate::testing::{FakeDruid, FakeResponse, FakeRoute};
+    use reqwest::StatusCode;
+    use serde_json::json;
+
+    fn cluster(strategy: RoutingStrategy) -> Cluster {
+        let endpoints = ["http://b1:8082", "http://b2:8082", "http://b3:8082"]
+            .into_iter()
+            .map(|endpoint| DruidEndpoint::parse(endpoint).unwrap())
+            .collect();
+        Cluster::new(
+            endpoints,
+            BalancerPolicy {
+                strategy,
+                failure_threshold: 2,
+                ..Default::default()
+            },
+        )
+    }
+
+    #[test]
+    fn test_routing() {
+        let cluster = cluster(RoutingStrategy::RoundRobin);
+        let picks: Vec<usize> = (0..6).map(|_| cluster.select(&[])).collect();
+        assert_eq!(picks, vec![0, 1, 2, 0, 1, 2]);
+        assert_eq!(cluster.select(&[0, 1]), 2);
+
+        cluster.nodes[1].record_failure(&cluster.policy);
+        assert!(!cluster.status()[1].circuit_open);
+        cluster.nodes[1].record_failure(&cluster.policy);
+        assert!(cluster.status()[1].circuit_open);
+        assert!((0..6).all(|_| cluster.select(&[]) != 1));
+
+        cluster.nodes[1].record_success();
+        assert!(!cluster.status()[1].circuit_open);
+
+        let cluster = self::cluster(RoutingStrategy::LeastInFlight);
+        let _busy = [cluster.nodes[0].begin(), cluster.nodes[2].begin()];
+        assert!((0..3).all(|_| cluster.select(&[]) == 1));
+    }
+
+    #[tokio::test]
+    async fn test_failover() {
+        let down = FakeDruid::start().await.unwrap();
+        let up = FakeDruid::start().await.unwrap();
+        down.respond_always(
+            FakeRoute::Sql,
+            FakeResponse::new(StatusCode::SERVICE_UNAVAILABLE, "upstream unavailable"),
+        );
+        down.respond_always(
+            FakeRoute::Status,
+            FakeResponse::new(StatusCode::SERVICE_UNAVAILABLE, ""),
+        );
+
+        let client = CathbadClient::new(CathbadClientConfig {
+            druid_endpoint: format!("{},{}", down.url(), up.url()),
+            ..Default::default()
+        })
+        .unwrap()
+        .with_balancer_policy(BalancerPolicy {
+            failure_threshold: 1,
+            health_check_interval: Some(Duration::from_millis(20)),
+            ..Default::default()
+        });
+
+        for _ in 0..4 {
+            let response = client.sql(SqlQuery::new("SELECT 1")).await.unwrap();
+            assert_eq!(response, SqlResponse::Objects(vec![]));
+        }
+        // The first request fails over, afterwards the open circuit keeps the node out
+        let sql = |fake: &FakeDruid| {
+            fake.requests()
+                .iter()
+                .filter(|r| r.path == "/druid/v2/sql")
+                .count()
+        };
+        assert_eq!(sql(&down), 1);
+        assert_eq!(sql(&up), 4);
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let status = client.cluster_status();
+        assert!(!status[0].healthy);
+        assert!(status[1].healthy);
+
+        client.cancel("dashboard-42").await.unwrap();
+        assert!(
+            up.requests()
+                .iter()
+                .any(|r| r.method == "DELETE" && r.path == "/druid/v2/dashboard-42")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_slow_node() {
+        let slow = FakeDruid::start().await.unwrap();
+        let fast = FakeDruid::start().await.unwrap();
+        slow.respond_always(
+            FakeRoute::Native,
+            FakeResponse::json(json!([])).with_delay(Duration::from_secs(5)),
+        );
+        let config = CathbadClientConfig {
+            druid_endpoint: format!("{},{}", slow.url(), fast.url()),
+            druid_timeout_ms: Some(100),
+            ..Default::default()
+        };
+        let query = DatasourceMetadataQuery {
+            data_source: DataSource::String("example".to_string()),
+            context: Some(Context {
+                query_id: Some("dashboard-42".to_string()),
+                ..Default::default()
+            }),
+        };
+        let native = |fake: &FakeDruid| {
+            fake.requests()
+                .iter()
+                .filter(|r| r.method == "POST" && r.path == "/druid/v2/")
+                .count()
+        };
+
+        // Without retries the timeout is the answer, the query isn't sent to the other node
+        let client = CathbadClient::new(config.clone()).unwrap();
+        let error = client.query(query.clone()).await.unwrap_err();
+        assert!(!error.is_node_failure());
+        assert_eq!(native(&slow), 1);
+        assert_eq!(native(&fast), 0);
+        assert!(!client.cluster_status()[0].circuit_open);
+
+        // A retry cancels the timed out query on its node before sending it again
+        let client = CathbadClient::new(config)
+            .unwrap()
+            .with_retry_policy(RetryPolicy {
+                max_attempts: 2,
+                initial_backoff: Duration::from_millis(10),
+                jitter: false,
+                ..Default::default()
+            });
+        client.query(query).await.unwrap();
+        assert_eq!(native(&slow), 2);
+        assert_eq!(native(&fast), 1);
+        let requests = slow.requests();
+        let last = requests.last().unwrap();
+        assert_eq!(
+            (last.method.as_str(), last.path.as_str()),
+            ("DELETE", "/druid/v2/dashboard-42")
+        );
+    }
+}
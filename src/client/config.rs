@@ -1,18 +1,533 @@
-use clap::Parser;
+use crate::client::{CathbadClientError, ExtraHeader, Secret};
+use crate::query::{Context, DruidVersion};
+use clap::parser::ValueSource;
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser};
+use serde::{Deserialize, Deserializer};
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
 #[derive(Debug, Clone, Parser)]
 pub struct CathbadClientConfig {
-    #[clap(long, default_value = "http://localhost")]
+    // TOML, or JSON when the file name ends in .json, see CathbadClientConfig::load
+    #[clap(long, env = "CATHBAD_CONFIG")]
+    pub druid_config: Option<PathBuf>,
+    // Section of the config file to use, e.g. prod, staging or local
+    #[clap(long, env = "CATHBAD_PROFILE")]
+    pub druid_profile: Option<String>,
+
+    // Base URL of a broker or router, optionally with a path prefix, see DruidEndpoint. Several
+    // comma-separated URLs spread queries over all of them, see BalancerPolicy
+    #[clap(
+        long,
+        env = "CATHBAD_ENDPOINT",
+        default_value = "http://localhost:8888"
+    )]
     pub druid_endpoint: String,
-    #[clap(long, default_value = "8888")]
-    pub druid_port: u32,
+    // Overrides the port of druid_endpoint
+    #[clap(long, env = "CATHBAD_PORT")]
+    pub druid_port: Option<u16>,
+    // Indented native query responses, for debugging
+    #[clap(long, env = "CATHBAD_PRETTY")]
+    pub druid_pretty: bool,
+
+    // druid-basic-security
+    #[clap(long, env = "CATHBAD_USERNAME")]
+    pub druid_username: Option<String>,
+    #[clap(long, env = "CATHBAD_PASSWORD", hide_env_values = true)]
+    pub druid_password: Option<Secret>,
+
+    // Static token sent as "Authorization: Bearer ...", see CathbadClient::with_token_provider
+    // for tokens that need refreshing
+    #[clap(long, env = "CATHBAD_BEARER_TOKEN", hide_env_values = true)]
+    pub druid_bearer_token: Option<Secret>,
+
+    // Repeatable, "Name: value". The environment variable takes one header per line.
+    #[clap(
+        long = "druid-header",
+        env = "CATHBAD_HEADERS",
+        value_delimiter = '\n',
+        hide_env_values = true
+    )]
+    pub druid_headers: Vec<ExtraHeader>,
+
+    // PEM bundle of additional root certificates, e.g. an internal CA
+    #[clap(long, env = "CATHBAD_CA_BUNDLE")]
+    pub druid_ca_bundle: Option<PathBuf>,
+    // Mutual TLS, PEM certificate chain and PKCS#8 PEM key
+    #[clap(long, env = "CATHBAD_CLIENT_CERT")]
+    pub druid_client_cert: Option<PathBuf>,
+    #[clap(long, env = "CATHBAD_CLIENT_KEY")]
+    pub druid_client_key: Option<PathBuf>,
+    // Skips certificate verification, development clusters only
+    #[clap(long, env = "CATHBAD_TLS_INSECURE")]
+    pub druid_tls_insecure: bool,
+
+    #[clap(long, env = "CATHBAD_CONNECT_TIMEOUT_MS")]
+    pub druid_connect_timeout_ms: Option<u64>,
+    // Whole request including the response body, so it also bounds streamed scans. The query
+    // context timeout is what stops the query on the broker.
+    #[clap(long, env = "CATHBAD_TIMEOUT_MS")]
+    pub druid_timeout_ms: Option<u64>,
+
+    // JSON object, applied to every query for the parameters its own context leaves unset
+    #[clap(long, env = "CATHBAD_CONTEXT", value_parser = parse_context)]
+    pub druid_context: Option<Context>,
+
+    // Release of the cluster, e.g. 0.22.1, or "auto" to ask GET /status once. Queries are adapted
+    // to it before they're sent, components it predates are rewritten or rejected.
+    #[clap(long, env = "CATHBAD_DRUID_VERSION")]
+    pub druid_version: Option<TargetVersion>,
+    // The cluster sets druid.javascript.enabled. Without it, and with druid_version set, queries
+    // with JavaScript filters, aggregators, post-aggregators or extraction functions are rejected.
+    #[clap(long, env = "CATHBAD_JAVASCRIPT")]
+    pub druid_javascript: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TargetVersion {
+    Auto,
+    Fixed(DruidVersion),
+}
+
+impl FromStr for TargetVersion {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "auto" => Ok(TargetVersion::Auto),
+            version => version.parse().map(TargetVersion::Fixed),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for TargetVersion {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+fn parse_context(value: &str) -> Result<Context, String> {
+    serde_json::from_str(value).map_err(|e| e.to_string())
+}
+
+impl CathbadClientConfig {
+    // Command line flags win over CATHBAD_* environment variables, which win over the config
+    // file, where the selected profile wins over [defaults]. Built-in defaults come last.
+    pub fn load() -> Result<Self, CathbadClientError> {
+        Self::from_matches(&Self::command().get_matches())
+    }
+
+    pub fn load_from<I, T>(args: I) -> Result<Self, CathbadClientError>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+    {
+        Self::load_from_env(args, |variable| std::env::var(variable).ok())
+    }
+
+    // Looks the CATHBAD_* variables up through env instead of the process environment. Those the
+    // command line leaves unset are passed on as flags.
+    pub fn load_from_env<I, T>(
+        args: I,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Result<Self, CathbadClientError>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+    {
+        let configuration = |reason: String| CathbadClientError::Configuration { reason };
+        let command = Self::command();
+        let variables: Vec<_> = command
+            .get_arguments()
+            .filter_map(|arg| {
+                let variable = arg.get_env()?.to_str()?.to_string();
+                let long = arg.get_long()?.to_string();
+                let takes_value = arg.get_action().takes_values();
+                Some((arg.get_id().to_string(), long, takes_value, variable))
+            })
+            .collect();
+        let command = command.mut_args(|arg| arg.env(None));
+
+        let mut args: Vec<OsString> = args.into_iter().map(Into::into).collect();
+        let matches = command
+            .clone()
+            .try_get_matches_from(&args)
+            .map_err(|e| configuration(e.to_string()))?;
+        for (id, long, takes_value, variable) in variables {
+            if matches.value_source(&id) == Some(ValueSource::CommandLine) {
+                continue;
+            }
+            // Empty variables count as unset, as with clap
+            let Some(value) = env(&variable).filter(|value| !value.is_empty()) else {
+                continue;
+            };
+            if takes_value {
+                args.push(format!("--{long}={value}").into());
+            } else if value
+                .parse()
+                .map_err(|_| configuration(format!("{variable} must be true or false")))?
+            {
+                args.push(format!("--{long}").into());
+            }
+        }
+        let matches = command
+            .try_get_matches_from(args)
+            .map_err(|e| configuration(e.to_string()))?;
+        Self::from_matches(&matches)
+    }
+
+    pub fn from_matches(matches: &ArgMatches) -> Result<Self, CathbadClientError> {
+        let mut config =
+            Self::from_arg_matches(matches).map_err(|e| CathbadClientError::Configuration {
+                reason: e.to_string(),
+            })?;
+        match &config.druid_config {
+            Some(path) => {
+                let settings = ConfigFile::read(path)?.select(config.druid_profile.as_deref())?;
+                config.apply(settings, matches);
+            }
+            None if config.druid_profile.is_some() => {
+                return Err(CathbadClientError::Configuration {
+                    reason: "a profile needs a config file".to_string(),
+                });
+            }
+            None => {}
+        }
+        Ok(config)
+    }
+
+    fn apply(&mut self, settings: FileSettings, matches: &ArgMatches) {
+        let unset = |id: &str| {
+            !matches!(
+                matches.value_source(id),
+                Some(ValueSource::CommandLine | ValueSource::EnvVariable)
+            )
+        };
+        macro_rules! layer {
+            ($field:ident, $setting:ident) => {
+                if let Some(value) = settings.$setting
+                    && unset(stringify!($field))
+                {
+                    self.$field = value.into();
+                }
+            };
+        }
+
+        layer!(druid_endpoint, endpoint);
+        layer!(druid_port, port);
+        layer!(druid_pretty, pretty);
+        layer!(druid_username, username);
+        layer!(druid_password, password);
+        layer!(druid_bearer_token, bearer_token);
+        layer!(druid_headers, headers);
+        layer!(druid_ca_bundle, ca_bundle);
+        layer!(druid_client_cert, client_cert);
+        layer!(druid_client_key, client_key);
+        layer!(druid_tls_insecure, tls_insecure);
+        layer!(druid_connect_timeout_ms, connect_timeout_ms);
+        layer!(druid_timeout_ms, timeout_ms);
+        layer!(druid_context, context);
+        layer!(druid_version, version);
+        layer!(druid_javascript, javascript);
+    }
 }
 
 impl Default for CathbadClientConfig {
     fn default() -> Self {
         Self {
-            druid_endpoint: "http://localhost".to_string(),
-            druid_port: 8888,
+            druid_config: None,
+            druid_profile: None,
+            druid_endpoint: "http://localhost:8888".to_string(),
+            druid_port: None,
+            druid_pretty: false,
+            druid_username: None,
+            druid_password: None,
+            druid_bearer_token: None,
+            druid_headers: vec![],
+            druid_ca_bundle: None,
+            druid_client_cert: None,
+            druid_client_key: None,
+            druid_tls_insecure: false,
+            druid_connect_timeout_ms: None,
+            druid_timeout_ms: None,
+            druid_context: None,
+            druid_version: None,
+            druid_javascript: false,
+        }
+    }
+}
+
+// Same settings as the flags, without the druid_ prefix
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FileSettings {
+    endpoint: Option<String>,
+    port: Option<u16>,
+    pretty: Option<bool>,
+    username: Option<String>,
+    password: Option<Secret>,
+    bearer_token: Option<Secret>,
+    headers: Option<Vec<ExtraHeader>>,
+    ca_bundle: Option<PathBuf>,
+    client_cert: Option<PathBuf>,
+    client_key: Option<PathBuf>,
+    tls_insecure: Option<bool>,
+    connect_timeout_ms: Option<u64>,
+    timeout_ms: Option<u64>,
+    context: Option<Context>,
+    version: Option<TargetVersion>,
+    javascript: Option<bool>,
+}
+
+impl FileSettings {
+    fn over(self, base: FileSettings) -> FileSettings {
+        let context = match (self.context, base.context) {
+            (Some(mut context), Some(defaults)) => {
+                context.apply_defaults(&defaults);
+                Some(context)
+            }
+            (context, defaults) => context.or(defaults),
+        };
+        FileSettings {
+            endpoint: self.endpoint.or(base.endpoint),
+            port: self.port.or(base.port),
+            pretty: self.pretty.or(base.pretty),
+            username: self.username.or(base.username),
+            password: self.password.or(base.password),
+            bearer_token: self.bearer_token.or(base.bearer_token),
+            headers: self.headers.or(base.headers),
+            ca_bundle: self.ca_bundle.or(base.ca_bundle),
+            client_cert: self.client_cert.or(base.client_cert),
+            client_key: self.client_key.or(base.client_key),
+            tls_insecure: self.tls_insecure.or(base.tls_insecure),
+            connect_timeout_ms: self.connect_timeout_ms.or(base.connect_timeout_ms),
+            timeout_ms: self.timeout_ms.or(base.timeout_ms),
+            context,
+            version: self.version.or(base.version),
+            javascript: self.javascript.or(base.javascript),
+        }
+    }
+
+    // Relative to the directory of the config file
+    fn resolve_paths(&mut self, dir: &Path) {
+        for path in [
+            &mut self.ca_bundle,
+            &mut self.client_cert,
+            &mut self.client_key,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            *path = dir.join(&*path);
         }
     }
 }
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ConfigFile {
+    // Used when no profile is given on the command line or in CATHBAD_PROFILE
+    profile: Option<String>,
+    defaults: FileSettings,
+    profiles: HashMap<String, FileSettings>,
+}
+
+impl ConfigFile {
+    fn read(path: &Path) -> Result<Self, CathbadClientError> {
+        let invalid = |reason: String| CathbadClientError::Configuration {
+            reason: format!("{}: {reason}", path.display()),
+        };
+        let contents = std::fs::read_to_string(path).map_err(|e| invalid(e.to_string()))?;
+        let mut file: ConfigFile = if path.extension().is_some_and(|ext| ext == "json") {
+            serde_json::from_str(&contents).map_err(|e| invalid(e.to_string()))?
+        } else {
+            toml::from_str(&contents).map_err(|e| invalid(e.to_string()))?
+        };
+
+        let dir = path.parent().unwrap_or(Path::new(""));
+        file.defaults.resolve_paths(dir);
+        for settings in file.profiles.values_mut() {
+            settings.resolve_paths(dir);
+        }
+        Ok(file)
+    }
+
+    fn select(mut self, profile: Option<&str>) -> Result<FileSettings, CathbadClientError> {
+        let Some(profile) = profile.map(str::to_string).or(self.profile.take()) else {
+            return Ok(self.defaults);
+        };
+        let settings =
+            self.profiles
+                .remove(&profile)
+                .ok_or_else(|| CathbadClientError::Configuration {
+                    reason: format!("no profile named {profile:?} in the config file"),
+                })?;
+        Ok(settings.over(self.defaults))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    fn write_temp(extension: &str, contents: &str) -> NamedTempFile {
+        let mut file = tempfile::Builder::new()
+            .prefix("cathbad-")
+            .suffix(&format!(".{extension}"))
+            .tempfile()
+            .unwrap();
+        file.write_all(contents.as_bytes()).unwrap();
+        file
+    }
+
+    #[test]
+    fn test_credentials_are_redacted() {
+        let config = CathbadClientConfig::parse_from([
+            "cathbad",
+            "--druid-username",
+            "admin",
+            "--druid-password",
+            "hunter2",
+            "--druid-bearer-token",
+            "eyJhbGciOi",
+            "--druid-header",
+            "X-Api-Key: abc123",
+            "--druid-header",
+            "X-Tenant: analytics",
+        ]);
+
+        assert_eq!(config.druid_password.as_ref().unwrap().expose(), "hunter2");
+        assert_eq!(config.druid_headers.len(), 2);
+
+        let debug = format!("{config:?}");
+        assert!(debug.contains("admin"));
+        assert!(!debug.contains("hunter2"));
+        assert!(!debug.contains("eyJhbGciOi"));
+        assert!(!debug.contains("abc123"));
+    }
+
+    #[test]
+    fn test_profiles() {
+        let file = write_temp(
+            "toml",
+            r#"
+            profile = "local"
+
+            [defaults]
+            username = "svc-analytics"
+            ca_bundle = "certs/ca.pem"
+            context = { priority = 10, timeout = 60000 }
+
+            [profiles.local]
+            endpoint = "http://localhost:8888"
+
+            [profiles.prod]
+            endpoint = "https://druid.example.com/analytics"
+            password = "hunter2"
+            headers = ["X-Tenant: analytics"]
+            context = { timeout = 30000 }
+            version = "0.22.1"
+            javascript = true
+            "#,
+        );
+        let path = file.path().to_str().unwrap();
+
+        let local =
+            CathbadClientConfig::load_from_env(["cathbad", "--druid-config", path], |_| None)
+                .unwrap();
+        assert_eq!(local.druid_endpoint, "http://localhost:8888");
+        assert_eq!(local.druid_username.as_deref(), Some("svc-analytics"));
+        assert!(local.druid_ca_bundle.unwrap().ends_with("certs/ca.pem"));
+
+        let prod = CathbadClientConfig::load_from_env(
+            [
+                "cathbad",
+                "--druid-config",
+                path,
+                "--druid-profile",
+                "prod",
+                "--druid-username",
+                "admin",
+            ],
+            |_| None,
+        )
+        .unwrap();
+        assert_eq!(prod.druid_endpoint, "https://druid.example.com/analytics");
+        assert_eq!(prod.druid_username.as_deref(), Some("admin"));
+        assert_eq!(prod.druid_password.unwrap().expose(), "hunter2");
+        assert_eq!(prod.druid_headers.len(), 1);
+        let context = prod.druid_context.unwrap();
+        assert_eq!(context.timeout, Some(30000));
+        assert_eq!(context.priority, Some(10));
+        assert_eq!(
+            prod.druid_version,
+            Some(TargetVersion::Fixed(DruidVersion::new(0, 22, 1)))
+        );
+        assert!(prod.druid_javascript);
+        assert!(!local.druid_javascript);
+
+        assert!(
+            CathbadClientConfig::load_from_env(
+                [
+                    "cathbad",
+                    "--druid-config",
+                    path,
+                    "--druid-profile",
+                    "staging",
+                ],
+                |_| None
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_precedence() {
+        let file = write_temp(
+            "json",
+            r#"{"defaults": {"timeout_ms": 1000, "connect_timeout_ms": 500, "port": 8082}}"#,
+        );
+        let env = |variable: &str| match variable {
+            "CATHBAD_TIMEOUT_MS" => Some("2000".to_string()),
+            "CATHBAD_PRETTY" => Some("true".to_string()),
+            _ => None,
+        };
+
+        let args = ["cathbad", "--druid-config", file.path().to_str().unwrap()];
+        let config = CathbadClientConfig::load_from_env(args, env).unwrap();
+        assert_eq!(config.druid_timeout_ms, Some(2000));
+        assert_eq!(config.druid_connect_timeout_ms, Some(500));
+        assert_eq!(config.druid_port, Some(8082));
+        assert!(config.druid_pretty);
+
+        let config = CathbadClientConfig::load_from_env(
+            args.into_iter().chain(["--druid-timeout-ms", "3000"]),
+            env,
+        )
+        .unwrap();
+        assert_eq!(config.druid_timeout_ms, Some(3000));
+
+        let config = CathbadClientConfig::load_from_env(args, |_| None).unwrap();
+        assert_eq!(config.druid_timeout_ms, Some(1000));
+        assert!(!config.druid_pretty);
+
+        let unknown = write_temp("toml", "[defaults]\nendpiont = \"http://localhost\"\n");
+        assert!(
+            CathbadClientConfig::load_from_env(
+                [
+                    "cathbad",
+                    "--druid-config",
+                    unknown.path().to_str().unwrap()
+                ],
+                |_| None
+            )
+            .is_err()
+        );
+    }
+}
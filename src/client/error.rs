@@ -1,11 +1,131 @@
-use crate::query::DruidQueryResponse;
+use crate::query::UnsupportedFeature;
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::time::Duration;
 
+#[derive(Debug)]
 pub enum CathbadClientError {
     InvalidQuery,
-    QueryMarshal { serde_error: serde_json::Error },
-    Reqwest { reqwest_error: reqwest::Error },
-    Druid { druid_error: DruidError },
-    DruidErrorUnmarshal,
+    // Caught before sending, the query reads columns that neither its data source nor its
+    // virtual columns have
+    UnresolvedColumns {
+        columns: Vec<String>,
+    },
+    QueryMarshal {
+        serde_error: serde_json::Error,
+    },
+    Reqwest {
+        reqwest_error: reqwest::Error,
+    },
+    Druid {
+        status: StatusCode,
+        druid_error: DruidError,
+        response: Box<DruidErrorResponse>,
+        retry_after: Option<Duration>,
+    },
+    // Non-2xx response without a Druid error body, e.g. from a proxy in front of the broker
+    DruidErrorUnmarshal {
+        status: StatusCode,
+        body: String,
+        retry_after: Option<Duration>,
+    },
+    MalformedStream,
+    DeadlineExceeded {
+        query_id: String,
+    },
+    Authentication {
+        reason: String,
+    },
+    Configuration {
+        reason: String,
+    },
+    // Caught before sending, the query uses something the target Druid version doesn't have
+    Unsupported {
+        unsupported: UnsupportedFeature,
+    },
+}
+
+impl CathbadClientError {
+    pub(crate) fn from_response(
+        status: StatusCode,
+        retry_after: Option<Duration>,
+        body: String,
+    ) -> Self {
+        match serde_json::from_str::<DruidErrorResponse>(&body) {
+            Ok(response) => Self::Druid {
+                status,
+                druid_error: DruidError::from(&response),
+                response: Box::new(response),
+                retry_after,
+            },
+            Err(_) => Self::DruidErrorUnmarshal {
+                status,
+                body,
+                retry_after,
+            },
+        }
+    }
+
+    pub fn status(&self) -> Option<StatusCode> {
+        match self {
+            Self::Druid { status, .. } | Self::DruidErrorUnmarshal { status, .. } => Some(*status),
+            Self::Reqwest { reqwest_error } => reqwest_error.status(),
+            _ => None,
+        }
+    }
+}
+
+impl Display for CathbadClientError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidQuery => write!(f, "query failed validation"),
+            Self::UnresolvedColumns { columns } => {
+                write!(f, "query reads unknown columns: {}", columns.join(", "))
+            }
+            Self::QueryMarshal { serde_error } => write!(f, "serialization failed: {serde_error}"),
+            Self::Reqwest { reqwest_error } => write!(f, "request failed: {reqwest_error}"),
+            Self::Druid {
+                status,
+                druid_error,
+                response,
+                ..
+            } => {
+                write!(f, "Druid returned {status}: {druid_error}")?;
+                if let Some(message) = &response.error_message {
+                    write!(f, ": {message}")?;
+                }
+                if let Some(host) = &response.host {
+                    write!(f, " (host {host})")?;
+                }
+                Ok(())
+            }
+            Self::DruidErrorUnmarshal { status, .. } => {
+                write!(f, "unexpected response with status {status}")
+            }
+            Self::MalformedStream => write!(f, "malformed or truncated streaming response"),
+            Self::DeadlineExceeded { query_id } => {
+                write!(f, "query {query_id} exceeded its deadline")
+            }
+            Self::Authentication { reason } => write!(f, "authentication failed: {reason}"),
+            Self::Configuration { reason } => write!(f, "invalid configuration: {reason}"),
+            Self::Unsupported { unsupported } => write!(f, "{unsupported}"),
+        }
+    }
+}
+
+impl Error for CathbadClientError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::QueryMarshal { serde_error } => Some(serde_error),
+            Self::Reqwest { reqwest_error } => Some(reqwest_error),
+            Self::Druid { druid_error, .. } => Some(druid_error),
+            Self::Unsupported { unsupported } => Some(unsupported),
+            _ => None,
+        }
+    }
 }
 
 impl From<serde_json::Error> for CathbadClientError {
@@ -22,40 +142,48 @@ impl From<reqwest::Error> for CathbadClientError {
     }
 }
 
-impl From<DruidError> for CathbadClientError {
-    fn from(value: DruidError) -> Self {
-        Self::Druid { druid_error: value }
+impl From<UnsupportedFeature> for CathbadClientError {
+    fn from(value: UnsupportedFeature) -> Self {
+        Self::Unsupported { unsupported: value }
     }
 }
 
-impl TryFrom<DruidQueryResponse> for DruidError {
-    type Error = CathbadClientError;
-
-    fn try_from(value: DruidQueryResponse) -> Result<Self, Self::Error> {
-        use DruidError::*;
-        match value {
-            DruidQueryResponse::Success {} => Err(CathbadClientError::DruidErrorUnmarshal),
-            DruidQueryResponse::Error { error, .. } => match error.as_str() {
-                "SQL parse failed" => Ok(SQLParseFailed),
-                "Plan validation failed" => Ok(PlanValidationFailed),
-                "Resource limit exceeded" => Ok(ResourceLimitExceeded),
-                "Query capacity exceeded" => Ok(QueryCapacityExceeded),
-                "Unsupported operation" => Ok(UnsupportedOperation),
-                "Query timeout" => Ok(QueryTimeout),
-                "Query interrupted" => Ok(QueryInterrupted),
-                "Query cancelled" => Ok(QueryCancelled),
-                "Truncated response context" => Ok(TruncatedResponseContext),
-                "Unknown exception" => Ok(UnknownException),
-                _ => Err(CathbadClientError::DruidErrorUnmarshal),
-            },
+// For errors that didn't come off the wire, the status and body are what Druid would have sent
+impl From<DruidError> for CathbadClientError {
+    fn from(value: DruidError) -> Self {
+        Self::Druid {
+            status: value.status(),
+            response: Box::new(DruidErrorResponse {
+                error: value.to_string(),
+                ..Default::default()
+            }),
+            druid_error: value,
+            retry_after: None,
         }
     }
 }
 
+// Covers both the legacy query error body (error, errorMessage, errorClass, host) and the
+// DruidException body of newer versions (error: "druidException", errorCode, persona, category, context)
+#[derive(Default, Debug, Clone, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DruidErrorResponse {
+    pub error: String,
+    pub error_message: Option<String>,
+    pub error_class: Option<String>,
+    pub host: Option<String>,
+    pub error_code: Option<String>,
+    pub persona: Option<String>,
+    pub category: Option<String>,
+    pub context: Option<Map<String, Value>>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
 pub enum DruidError {
     SQLParseFailed,           // 400
     PlanValidationFailed,     // 400
     ResourceLimitExceeded,    // 400
+    InvalidInput,             // 400
     QueryCapacityExceeded,    // 429
     UnsupportedOperation,     // 501
     QueryTimeout,             // 504
@@ -63,4 +191,148 @@ pub enum DruidError {
     QueryCancelled,           // 500
     TruncatedResponseContext, // 500
     UnknownException,         // 500
+    Other(String),
+}
+
+impl DruidError {
+    pub fn status(&self) -> StatusCode {
+        use DruidError::*;
+        match self {
+            SQLParseFailed | PlanValidationFailed | ResourceLimitExceeded | InvalidInput => {
+                StatusCode::BAD_REQUEST
+            }
+            QueryCapacityExceeded => StatusCode::TOO_MANY_REQUESTS,
+            UnsupportedOperation => StatusCode::NOT_IMPLEMENTED,
+            QueryTimeout => StatusCode::GATEWAY_TIMEOUT,
+            QueryInterrupted
+            | QueryCancelled
+            | TruncatedResponseContext
+            | UnknownException
+            | Other(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+impl Display for DruidError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        use DruidError::*;
+        f.write_str(match self {
+            SQLParseFailed => "SQL parse failed",
+            PlanValidationFailed => "Plan validation failed",
+            ResourceLimitExceeded => "Resource limit exceeded",
+            InvalidInput => "Invalid input",
+            QueryCapacityExceeded => "Query capacity exceeded",
+            UnsupportedOperation => "Unsupported operation",
+            QueryTimeout => "Query timeout",
+            QueryInterrupted => "Query interrupted",
+            QueryCancelled => "Query cancelled",
+            TruncatedResponseContext => "Truncated response context",
+            UnknownException => "Unknown exception",
+            Other(error) => error,
+        })
+    }
+}
+
+impl Error for DruidError {}
+
+impl From<&DruidErrorResponse> for DruidError {
+    fn from(value: &DruidErrorResponse) -> Self {
+        use DruidError::*;
+        match (value.error.as_str(), value.category.as_deref()) {
+            ("druidException", Some("INVALID_INPUT")) => InvalidInput,
+            ("druidException", Some("CAPACITY_EXCEEDED")) => QueryCapacityExceeded,
+            ("druidException", Some("TIMEOUT")) => QueryTimeout,
+            ("druidException", Some("CANCELED")) => QueryCancelled,
+            ("druidException", Some("UNSUPPORTED")) => UnsupportedOperation,
+            ("druidException", Some("RUNTIME_FAILURE" | "DEFENSIVE" | "UNCATEGORIZED")) => {
+                UnknownException
+            }
+            ("druidException", Some(category)) => Other(category.to_string()),
+            ("SQL parse failed", _) => SQLParseFailed,
+            ("Plan validation failed", _) => PlanValidationFailed,
+            ("Resource limit exceeded", _) => ResourceLimitExceeded,
+            ("Query capacity exceeded", _) => QueryCapacityExceeded,
+            ("Unsupported operation", _) => UnsupportedOperation,
+            ("Query timeout", _) => QueryTimeout,
+            ("Query interrupted", _) => QueryInterrupted,
+            ("Query cancelled", _) => QueryCancelled,
+            ("Truncated response context", _) => TruncatedResponseContext,
+            ("Unknown exception", _) => UnknownException,
+            (error, _) => Other(error.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_legacy_error_body() {
+        let body = r#"{"error":"Query timeout","errorMessage":"Timeout waiting for task.","errorClass":"java.util.concurrent.TimeoutException","host":"druid1.example.com:8083"}"#;
+        let error =
+            CathbadClientError::from_response(StatusCode::GATEWAY_TIMEOUT, None, body.to_string());
+
+        let CathbadClientError::Druid {
+            druid_error,
+            response,
+            ..
+        } = &error
+        else {
+            panic!("expected a Druid error, got {error:?}");
+        };
+        assert_eq!(druid_error, &DruidError::QueryTimeout);
+        assert_eq!(response.host.as_deref(), Some("druid1.example.com:8083"));
+        assert_eq!(
+            error.to_string(),
+            "Druid returned 504 Gateway Timeout: Query timeout: Timeout waiting for task. (host druid1.example.com:8083)"
+        );
+        let source = error.source().unwrap();
+        assert_eq!(
+            source.downcast_ref::<DruidError>(),
+            Some(&DruidError::QueryTimeout)
+        );
+    }
+
+    #[test]
+    fn test_druid_exception_body() {
+        let body = r#"{"error":"druidException","errorCode":"invalidInput","persona":"USER","category":"INVALID_INPUT","errorMessage":"Column 'foo' not found","context":{"line":"1"}}"#;
+        let error =
+            CathbadClientError::from_response(StatusCode::BAD_REQUEST, None, body.to_string());
+        let CathbadClientError::Druid {
+            druid_error,
+            response,
+            ..
+        } = error
+        else {
+            panic!("expected a Druid error");
+        };
+        assert_eq!(druid_error, DruidError::InvalidInput);
+        assert_eq!(response.error_code.as_deref(), Some("invalidInput"));
+        assert_eq!(response.context.unwrap()["line"], "1");
+
+        let body = r#"{"error":"Some new failure","errorMessage":null}"#;
+        let error = CathbadClientError::from_response(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            None,
+            body.to_string(),
+        );
+        assert!(matches!(
+            error,
+            CathbadClientError::Druid { druid_error: DruidError::Other(ref e), .. } if e == "Some new failure"
+        ));
+
+        let error =
+            CathbadClientError::from_response(StatusCode::BAD_GATEWAY, None, "<html>".to_string());
+        assert!(matches!(
+            error,
+            CathbadClientError::DruidErrorUnmarshal { ref body, .. } if body == "<html>"
+        ));
+    }
+
+    #[test]
+    fn test_boxed_error() {
+        let error: Box<dyn Error + Send + Sync> = CathbadClientError::InvalidQuery.into();
+        assert_eq!(error.to_string(), "query failed validation");
+    }
 }
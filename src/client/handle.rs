@@ -0,0 +1,142 @@
+use crate::client::{CathbadClient, CathbadClientError, DruidClient};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::time::Sleep;
+
+type QueryFuture<'a, R> = Pin<Box<dyn Future<Output = Result<R, CathbadClientError>> + Send + 'a>>;
+
+// A submitted query that knows its Druid queryId. If the handle is dropped before the query
+// finishes, or its deadline passes, the query is cancelled on the broker as well. Nothing is
+// cancelled for a query that was never sent.
+pub struct QueryHandle<'a, R> {
+    query_id: String,
+    client: &'a CathbadClient,
+    future: QueryFuture<'a, R>,
+    deadline: Option<Pin<Box<Sleep>>>,
+    // Set by the future right before the query goes out
+    sent: Arc<AtomicBool>,
+    finished: bool,
+}
+
+impl<'a, R> QueryHandle<'a, R> {
+    pub(crate) fn new(
+        query_id: String,
+        client: &'a CathbadClient,
+        sent: Arc<AtomicBool>,
+        future: QueryFuture<'a, R>,
+    ) -> Self {
+        Self {
+            query_id,
+            client,
+            future,
+            deadline: None,
+            sent,
+            finished: false,
+        }
+    }
+
+    pub fn query_id(&self) -> &str {
+        &self.query_id
+    }
+
+    pub fn with_deadline(mut self, deadline: Duration) -> Self {
+        self.deadline = Some(Box::pin(tokio::time::sleep(deadline)));
+        self
+    }
+
+    fn cancel_in_background(&mut self) {
+        if self.sent.load(Ordering::Acquire) {
+            cancel_in_background(self.client.clone(), self.query_id.clone());
+        }
+    }
+}
+
+// Without a runtime there is nothing to drive the request, the broker-side timeout applies
+pub(crate) fn cancel_in_background(client: CathbadClient, query_id: String) {
+    if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+        runtime.spawn(async move {
+            let _ = client.cancel(&query_id).await;
+        });
+    }
+}
+
+impl<R> Future for QueryHandle<'_, R> {
+    type Output = Result<R, CathbadClientError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+
+        if let Poll::Ready(result) = this.future.as_mut().poll(cx) {
+            this.finished = true;
+            return Poll::Ready(result);
+        }
+
+        if let Some(deadline) = this.deadline.as_mut()
+            && deadline.as_mut().poll(cx).is_ready()
+        {
+            this.finished = true;
+            this.cancel_in_background();
+            return Poll::Ready(Err(CathbadClientError::DeadlineExceeded {
+                query_id: this.query_id.clone(),
+            }));
+        }
+
+        Poll::Pending
+    }
+}
+
+impl<R> Drop for QueryHandle<'_, R> {
+    fn drop(&mut self) {
+        if !self.finished {
+            self.finished = true;
+            self.cancel_in_background();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::client::{CathbadClient, CathbadClientError};
+    use crate::query::{DataSource, DatasourceMetadataQuery};
+    use crate::testing::{FakeDruid, FakeResponse, FakeRoute};
+    use serde_json::json;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_deadline_cancels_query() {
+        let fake = FakeDruid::start().await.unwrap();
+        fake.respond(
+            FakeRoute::Native,
+            FakeResponse::json(json!([])).with_delay(Duration::from_secs(5)),
+        );
+        let client = CathbadClient::new(fake.config()).unwrap();
+        let query = DatasourceMetadataQuery {
+            data_source: DataSource::String("wikipedia".to_string()),
+            context: None,
+        };
+
+        let handle = client
+            .submit(query)
+            .with_deadline(Duration::from_millis(50));
+        let query_id = handle.query_id().to_string();
+        let error = handle.await.unwrap_err();
+        assert!(matches!(error, CathbadClientError::DeadlineExceeded { .. }));
+
+        for _ in 0..100 {
+            if fake.requests().len() == 2 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let requests = fake.requests();
+        assert_eq!(requests[1].method, "DELETE");
+        assert_eq!(requests[1].path, format!("/druid/v2/{query_id}"));
+
+        // The handle is done once the deadline fired, dropping it doesn't cancel again
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(fake.requests().len(), 2);
+    }
+}
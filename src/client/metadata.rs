@@ -0,0 +1,219 @@
+use crate::client::{CathbadClient, CathbadClientError, DruidEndpoint};
+use crate::query::Interval;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+// GET /status
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerStatus {
+    pub version: String,
+    #[serde(default)]
+    pub modules: Vec<ServerModule>,
+    pub memory: Option<ServerMemory>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerModule {
+    pub name: String,
+    pub artifact: Option<String>,
+    pub version: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerMemory {
+    pub max_memory: Option<u64>,
+    pub total_memory: Option<u64>,
+    pub free_memory: Option<u64>,
+    pub used_memory: Option<u64>,
+    pub direct_memory: Option<u64>,
+}
+
+// GET /druid/v2/datasources/{name}, the columns of the segments the broker knows about
+#[derive(Debug, Clone, Default, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(default)]
+pub struct DatasourceSchema {
+    pub dimensions: Vec<String>,
+    pub metrics: Vec<String>,
+}
+
+// GET /druid/v2/datasources/{name}/candidates, the servers a query would be sent to
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SegmentCandidate {
+    pub interval: Interval,
+    pub version: String,
+    pub partition_number: u32,
+    pub size: Option<u64>,
+    #[serde(default)]
+    pub locations: Vec<SegmentLocation>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SegmentLocation {
+    pub name: String,
+    pub host: Option<String>,
+    pub host_and_tls_port: Option<String>,
+    pub max_size: Option<u64>,
+    #[serde(rename = "type")]
+    pub type_: Option<String>,
+    pub tier: Option<String>,
+    pub priority: Option<i32>,
+}
+
+impl CathbadClient {
+    pub async fn status(&self) -> Result<ServerStatus, CathbadClientError> {
+        self.get_json(DruidEndpoint::status).await
+    }
+
+    pub async fn health(&self) -> Result<bool, CathbadClientError> {
+        self.get_json(DruidEndpoint::health).await
+    }
+
+    // Runtime properties of the node, Druid masks the ones it considers secret
+    pub async fn properties(&self) -> Result<HashMap<String, String>, CathbadClientError> {
+        self.get_json(DruidEndpoint::properties).await
+    }
+
+    // Only datasources with segments queryable on the broker
+    pub async fn datasources(&self) -> Result<Vec<String>, CathbadClientError> {
+        self.get_json(DruidEndpoint::datasources).await
+    }
+
+    pub async fn datasource(&self, name: &str) -> Result<DatasourceSchema, CathbadClientError> {
+        self.get_json(|endpoint| endpoint.datasource(name)).await
+    }
+
+    // All segments when intervals is None
+    pub async fn candidates(
+        &self,
+        name: &str,
+        intervals: Option<&[Interval]>,
+    ) -> Result<Vec<SegmentCandidate>, CathbadClientError> {
+        let intervals = intervals.map(|intervals| {
+            intervals
+                .iter()
+                .map(Interval::to_string)
+                .collect::<Vec<_>>()
+                .join(",")
+        });
+        self.get_json(|endpoint| {
+            let mut url = endpoint.candidates(name);
+            if let Some(intervals) = &intervals {
+                url.query_pairs_mut().append_pair("intervals", intervals);
+            }
+            url
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::client::CathbadClient;
+    use crate::testing::{FakeDruid, FakeResponse, FakeRoute};
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_status() {
+        let fake = FakeDruid::start().await.unwrap();
+        fake.respond(
+            FakeRoute::Status,
+            FakeResponse::json(json!({
+                "version": "31.0.0",
+                "modules": [{
+                    "name": "org.apache.druid.query.aggregation.datasketches.theta.SketchModule",
+                    "artifact": "druid-datasketches",
+                    "version": "31.0.0"
+                }],
+                "memory": {"maxMemory": 1073741824, "totalMemory": 536870912, "freeMemory": 268435456, "usedMemory": 268435456, "directMemory": 134217728}
+            })),
+        )
+        .respond(
+            FakeRoute::Status,
+            FakeResponse::json(json!({"druid.service": "druid/broker"})),
+        );
+        let client = CathbadClient::new(fake.config()).unwrap();
+
+        let status = client.status().await.unwrap();
+        assert_eq!(status.version, "31.0.0");
+        assert_eq!(
+            status.modules[0].artifact.as_deref(),
+            Some("druid-datasketches")
+        );
+        assert_eq!(status.memory.unwrap().max_memory, Some(1073741824));
+
+        let properties = client.properties().await.unwrap();
+        assert_eq!(properties["druid.service"], "druid/broker");
+        assert!(client.health().await.unwrap());
+
+        let paths: Vec<String> = fake.requests().into_iter().map(|r| r.path).collect();
+        assert_eq!(
+            paths,
+            vec!["/status", "/status/properties", "/status/health"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_datasources() {
+        let fake = FakeDruid::start().await.unwrap();
+        fake.respond(
+            FakeRoute::Datasources,
+            FakeResponse::json(json!(["wikipedia", "koalas"])),
+        )
+        .respond(
+            FakeRoute::Datasources,
+            FakeResponse::json(json!({"dimensions": ["page", "user"], "metrics": ["count"]})),
+        )
+        .respond(
+            FakeRoute::Datasources,
+            FakeResponse::json(json!([{
+                "interval": "2015-09-12T00:00:00.000Z/2015-09-13T00:00:00.000Z",
+                "version": "2024-01-01T00:00:00.000Z",
+                "partitionNumber": 0,
+                "size": 4528,
+                "locations": [{
+                    "name": "historical1:8083",
+                    "host": "historical1:8083",
+                    "hostAndTlsPort": null,
+                    "maxSize": 300000000000u64,
+                    "type": "historical",
+                    "tier": "_default_tier",
+                    "priority": 0
+                }]
+            }])),
+        );
+        let client = CathbadClient::new(fake.config()).unwrap();
+
+        assert_eq!(
+            client.datasources().await.unwrap(),
+            vec!["wikipedia", "koalas"]
+        );
+        let schema = client.datasource("wikipedia").await.unwrap();
+        assert_eq!(schema.dimensions, vec!["page", "user"]);
+        let candidates = client
+            .candidates(
+                "wikipedia",
+                Some(&[
+                    "2015-09-12/2015-09-13".parse().unwrap(),
+                    "2015-09-14/P1D".parse().unwrap(),
+                ]),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            candidates[0].locations[0].type_.as_deref(),
+            Some("historical")
+        );
+
+        let requests = fake.requests();
+        assert_eq!(requests[1].path, "/druid/v2/datasources/wikipedia");
+        assert_eq!(
+            requests[2].path,
+            "/druid/v2/datasources/wikipedia/candidates?intervals=2015-09-12T00%3A00%3A00.000Z%2F2015-09-13T00%3A00%3A00.000Z%2C2015-09-14T00%3A00%3A00.000Z%2FP1D"
+        );
+    }
+}
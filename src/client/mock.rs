@@ -0,0 +1,221 @@
+use crate::client::{CathbadClientError, DruidClient, DruidError};
+use crate::query::{
+    DataSource, NativeQuery, NativeQueryType, SqlQuery, SqlResponse, TypeConstrainedQuery,
+};
+use serde_json::Value;
+use std::sync::Mutex;
+
+#[derive(Debug, Clone)]
+pub enum MockResponse {
+    Json(Value),
+    // Raw body, e.g. for SQL csv or *Lines result formats
+    Body(String),
+    Error(DruidError),
+}
+
+#[derive(Debug)]
+struct MockRule {
+    query_type: NativeQueryType,
+    data_source: Option<String>,
+    response: MockResponse,
+}
+
+// In-memory DruidClient for tests. Records every query it receives and answers with the canned
+// response registered for the query type, preferring rules that also name the data source.
+#[derive(Debug, Default)]
+pub struct MockDruidClient {
+    rules: Mutex<Vec<MockRule>>,
+    sql_response: Mutex<Option<MockResponse>>,
+    queries: Mutex<Vec<NativeQuery>>,
+    sql_queries: Mutex<Vec<SqlQuery>>,
+    cancelled: Mutex<Vec<String>>,
+}
+
+impl MockDruidClient {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn respond(&self, query_type: NativeQueryType, response: MockResponse) -> &Self {
+        self.rules.lock().unwrap().push(MockRule {
+            query_type,
+            data_source: None,
+            response,
+        });
+        self
+    }
+
+    pub fn respond_for(
+        &self,
+        query_type: NativeQueryType,
+        data_source: &str,
+        response: MockResponse,
+    ) -> &Self {
+        self.rules.lock().unwrap().push(MockRule {
+            query_type,
+            data_source: Some(data_source.to_string()),
+            response,
+        });
+        self
+    }
+
+    pub fn respond_sql(&self, response: MockResponse) -> &Self {
+        *self.sql_response.lock().unwrap() = Some(response);
+        self
+    }
+
+    pub fn queries(&self) -> Vec<NativeQuery> {
+        self.queries.lock().unwrap().clone()
+    }
+
+    pub fn sql_queries(&self) -> Vec<SqlQuery> {
+        self.sql_queries.lock().unwrap().clone()
+    }
+
+    pub fn cancelled(&self) -> Vec<String> {
+        self.cancelled.lock().unwrap().clone()
+    }
+
+    fn find_response(&self, query: &NativeQuery) -> Option<MockResponse> {
+        let query_type = query.query_type();
+        let data_source = match query.data_source() {
+            DataSource::String(name) | DataSource::Table { name } => Some(name.as_str()),
+            _ => None,
+        };
+
+        let rules = self.rules.lock().unwrap();
+        let matching = |rule: &&MockRule| rule.query_type == query_type;
+        rules
+            .iter()
+            .filter(matching)
+            .find(|rule| rule.data_source.is_some() && rule.data_source.as_deref() == data_source)
+            .or_else(|| {
+                rules
+                    .iter()
+                    .filter(matching)
+                    .find(|rule| rule.data_source.is_none())
+            })
+            .map(|rule| rule.response.clone())
+    }
+}
+
+fn into_body(response: MockResponse) -> Result<String, CathbadClientError> {
+    match response {
+        MockResponse::Json(value) => Ok(value.to_string()),
+        MockResponse::Body(body) => Ok(body),
+        MockResponse::Error(druid_error) => Err(druid_error.into()),
+    }
+}
+
+impl DruidClient for MockDruidClient {
+    async fn query<Q: TypeConstrainedQuery>(
+        &self,
+        query: Q,
+    ) -> Result<Q::Response, CathbadClientError> {
+        let native: NativeQuery = query.clone().into();
+        let response = self.find_response(&native);
+        self.queries.lock().unwrap().push(native);
+
+        let response = response.ok_or_else(|| CathbadClientError::Configuration {
+            reason: format!(
+                "MockDruidClient has no response for {:?} queries",
+                query.query_type()
+            ),
+        })?;
+        Ok(query.parse_response(&into_body(response)?)?)
+    }
+
+    async fn sql(&self, query: SqlQuery) -> Result<SqlResponse, CathbadClientError> {
+        let response = self.sql_response.lock().unwrap().clone();
+        let result_format = query.result_format.clone().unwrap_or_default();
+        self.sql_queries.lock().unwrap().push(query);
+
+        let response = response.ok_or_else(|| CathbadClientError::Configuration {
+            reason: "MockDruidClient has no response for SQL queries".to_string(),
+        })?;
+        Ok(SqlResponse::from_response(
+            &result_format,
+            &into_body(response)?,
+        )?)
+    }
+
+    async fn cancel(&self, query_id: &str) -> Result<(), CathbadClientError> {
+        self.cancelled.lock().unwrap().push(query_id.to_string());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::query::{DatasourceMetadataQuery, SqlResultFormat};
+    use serde_json::json;
+
+    fn metadata_query(data_source: &str) -> DatasourceMetadataQuery {
+        DatasourceMetadataQuery {
+            data_source: DataSource::String(data_source.to_string()),
+            context: None,
+        }
+    }
+
+    // Code under test only depends on the trait
+    async fn latest_event<C: DruidClient>(
+        client: &C,
+        data_source: &str,
+    ) -> Result<String, CathbadClientError> {
+        let rows = client.query(metadata_query(data_source)).await?;
+        Ok(rows[0].result.max_ingested_event_time.clone())
+    }
+
+    #[tokio::test]
+    async fn test_canned_responses() {
+        let mock = MockDruidClient::new();
+        mock.respond(
+            NativeQueryType::DatasourceMetadata,
+            MockResponse::Json(json!([{
+                "timestamp": "2013-05-09T18:24:00.000Z",
+                "result": {"maxIngestedEventTime": "2013-05-09T18:24:09.007Z"}
+            }])),
+        )
+        .respond_for(
+            NativeQueryType::DatasourceMetadata,
+            "broken",
+            MockResponse::Error(DruidError::QueryCapacityExceeded),
+        );
+
+        assert_eq!(
+            latest_event(&mock, "wikipedia").await.unwrap(),
+            "2013-05-09T18:24:09.007Z"
+        );
+        let error = latest_event(&mock, "broken").await.unwrap_err();
+        assert!(matches!(
+            error,
+            CathbadClientError::Druid {
+                druid_error: DruidError::QueryCapacityExceeded,
+                ..
+            }
+        ));
+
+        let recorded = mock.queries();
+        assert_eq!(recorded.len(), 2);
+        assert!(matches!(recorded[1].data_source(), DataSource::String(name) if name == "broken"));
+    }
+
+    #[tokio::test]
+    async fn test_sql_and_missing_responses() {
+        let mock = MockDruidClient::new();
+        assert!(mock.query(metadata_query("wikipedia")).await.is_err());
+
+        mock.respond_sql(MockResponse::Body("[\"Main\",3]\n\n".to_string()));
+        let mut query = SqlQuery::new("SELECT page, COUNT(*) FROM wikipedia GROUP BY 1");
+        query.result_format = Some(SqlResultFormat::ArrayLines);
+        let response = mock.sql(query).await.unwrap();
+        assert_eq!(
+            response,
+            SqlResponse::Arrays(vec![vec![json!("Main"), json!(3)]])
+        );
+
+        mock.cancel("abandoned").await.unwrap();
+        assert_eq!(mock.cancelled(), vec!["abandoned".to_string()]);
+    }
+}
@@ -1,7 +1,24 @@
+mod auth;
+mod cluster;
 mod config;
+mod endpoint;
 mod error;
+mod handle;
+mod metadata;
+mod mock;
 mod model;
+mod retry;
+mod stream;
+mod tls;
 
+pub use auth::*;
+pub use cluster::*;
 pub use config::*;
+pub use endpoint::*;
 pub use error::*;
+pub use handle::*;
+pub use metadata::*;
+pub use mock::*;
 pub use model::*;
+pub use retry::*;
+pub use stream::*;
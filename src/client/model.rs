@@ -1,85 +1,677 @@
-use crate::client::{CathbadClientConfig, CathbadClientError};
-use crate::query::{DruidQueryResponse, TypeConstrainedQuery};
-use reqwest::Client;
-use reqwest::header::{ACCEPT, CONTENT_TYPE, HeaderMap};
-use serde::{Deserialize, Serialize};
-use std::cmp::PartialEq;
-
-trait DruidClient {
-    async fn query(
+use crate::client::tls::configure_tls;
+use crate::client::{
+    BalancerPolicy, CathbadClientConfig, CathbadClientError, Cluster, DruidEndpoint, NodeStatus,
+    QueryHandle, RetryPolicy, ScanStream, TargetVersion, TokenProvider,
+};
+use crate::query::{
+    Context, DataSource, DruidVersion, ScanQuery, SqlContext, SqlQuery, SqlResponse,
+    TypeConstrainedQuery, reject_javascript,
+};
+use futures_util::future::join_all;
+use reqwest::header::{ACCEPT, CONTENT_TYPE, HeaderMap, RETRY_AFTER};
+use reqwest::{Client, RequestBuilder, Response, StatusCode, Url};
+use serde::de::DeserializeOwned;
+use std::fmt::{Debug, Formatter};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use uuid::Uuid;
+
+pub trait DruidClient {
+    fn query<Q: TypeConstrainedQuery>(
+        &self,
+        query: Q,
+    ) -> impl Future<Output = Result<Q::Response, CathbadClientError>> + Send;
+
+    fn sql(
         &self,
-        query: impl TypeConstrainedQuery,
-    ) -> Result<DruidQueryResponse, CathbadClientError>;
+        query: SqlQuery,
+    ) -> impl Future<Output = Result<SqlResponse, CathbadClientError>> + Send;
+
+    fn cancel(&self, query_id: &str)
+    -> impl Future<Output = Result<(), CathbadClientError>> + Send;
 }
 
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct CathbadClient {
     config: CathbadClientConfig,
+    cluster: Arc<Cluster>,
     client: Client,
+    token_provider: Option<Arc<dyn TokenProvider>>,
+    retry_policy: RetryPolicy,
+    column_check: bool,
+    // Detected version, shared by clones so /status is only asked once
+    detected_version: Arc<OnceLock<DruidVersion>>,
 }
 
 impl CathbadClient {
-    pub fn new(config: CathbadClientConfig) -> Self {
+    pub fn new(config: CathbadClientConfig) -> Result<Self, CathbadClientError> {
         let mut headers = HeaderMap::new();
         headers.insert(CONTENT_TYPE, "application/json".parse().unwrap());
         headers.insert(ACCEPT, "application/json".parse().unwrap());
-        Self {
+        for header in &config.druid_headers {
+            headers.insert(header.name.clone(), header.value.clone());
+        }
+        let endpoints = DruidEndpoint::from_config(&config)?;
+        let mut builder = configure_tls(Client::builder().default_headers(headers), &config)?;
+        if let Some(timeout) = config.druid_connect_timeout_ms {
+            builder = builder.connect_timeout(Duration::from_millis(timeout));
+        }
+        if let Some(timeout) = config.druid_timeout_ms {
+            builder = builder.timeout(Duration::from_millis(timeout));
+        }
+        Ok(Self {
             config,
-            client: Client::builder().default_headers(headers).build().unwrap(),
+            cluster: Arc::new(Cluster::new(endpoints, BalancerPolicy::default())),
+            client: builder.build()?,
+            token_provider: None,
+            retry_policy: RetryPolicy::none(),
+            column_check: false,
+            detected_version: Arc::new(OnceLock::new()),
+        })
+    }
+
+    // Takes precedence over the static credentials in the config
+    pub fn with_token_provider(mut self, provider: impl TokenProvider + 'static) -> Self {
+        self.token_provider = Some(Arc::new(provider));
+        self
+    }
+
+    // Retries reuse the request as sent, including its queryId, so cancellation keeps working
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    // Looks up the columns of table data sources with CathbadClient::datasource before each query.
+    // Costs a request, and the broker only reports the columns of recent segments by default.
+    pub fn with_column_check(mut self) -> Self {
+        self.column_check = true;
+        self
+    }
+
+    // Only matters with several endpoints configured
+    pub fn with_balancer_policy(mut self, balancer_policy: BalancerPolicy) -> Self {
+        let endpoints = self.endpoints().cloned().collect();
+        self.cluster = Arc::new(Cluster::new(endpoints, balancer_policy));
+        self
+    }
+
+    pub fn endpoints(&self) -> impl Iterator<Item = &DruidEndpoint> {
+        self.cluster.nodes().iter().map(|node| &node.endpoint)
+    }
+
+    pub fn cluster_status(&self) -> Vec<NodeStatus> {
+        self.cluster.status()
+    }
+
+    // None leaves queries as they are, without druid_version nothing is adapted
+    pub async fn target_version(&self) -> Result<Option<DruidVersion>, CathbadClientError> {
+        match self.config.druid_version {
+            None => Ok(None),
+            Some(TargetVersion::Fixed(version)) => Ok(Some(version)),
+            Some(TargetVersion::Auto) => {
+                if let Some(version) = self.detected_version.get() {
+                    return Ok(Some(*version));
+                }
+                let version = self.detect_version().await?;
+                Ok(Some(*self.detected_version.get_or_init(|| version)))
+            }
+        }
+    }
+
+    pub async fn detect_version(&self) -> Result<DruidVersion, CathbadClientError> {
+        let status = self.status().await?;
+        status
+            .version
+            .parse()
+            .map_err(|reason| CathbadClientError::Configuration { reason })
+    }
+
+    async fn adapt<Q: TypeConstrainedQuery>(
+        &self,
+        query: &mut Q,
+    ) -> Result<(), CathbadClientError> {
+        if let Some(version) = self.target_version().await? {
+            query.adapt(&version)?;
+            if !self.config.druid_javascript {
+                reject_javascript(query, &version)?;
+            }
+        }
+        Ok(())
+    }
+
+    // Inline data sources carry their columns, tables only have them looked up with column checks
+    async fn known_columns(
+        &self,
+        mut data_source: &DataSource,
+    ) -> Result<Option<Vec<String>>, CathbadClientError> {
+        let mut unnested = vec![];
+        while let DataSource::Unnest {
+            base,
+            virtual_column,
+            ..
+        } = data_source
+        {
+            unnested.push(virtual_column.name().to_string());
+            data_source = base;
+        }
+        let known = match data_source {
+            DataSource::Inline { column_names, .. } => Some(column_names.clone()),
+            DataSource::Table { name } | DataSource::String(name) if self.column_check => {
+                let schema = self.datasource(name).await?;
+                Some(
+                    schema
+                        .dimensions
+                        .into_iter()
+                        .chain(schema.metrics)
+                        .collect(),
+                )
+            }
+            _ => None,
+        };
+        Ok(known.map(|mut known: Vec<String>| {
+            known.extend(unnested);
+            known
+        }))
+    }
+
+    async fn validate<Q: TypeConstrainedQuery>(&self, query: &Q) -> Result<(), CathbadClientError> {
+        if !query.validate_type() || !query.validate_subcomponents() {
+            return Err(CathbadClientError::InvalidQuery);
+        }
+        let Some(known) = self.known_columns(query.data_source()).await? else {
+            return Ok(());
+        };
+        let unresolved = query.unresolved_columns(&known);
+        if !unresolved.is_empty() {
+            return Err(CathbadClientError::UnresolvedColumns {
+                columns: unresolved.into_iter().map(String::from).collect(),
+            });
+        }
+        Ok(())
+    }
+
+    // Assigns a queryId when the query context doesn't carry one, so the query can be cancelled
+    pub fn submit<'a, Q: TypeConstrainedQuery + 'a>(
+        &'a self,
+        mut query: Q,
+    ) -> QueryHandle<'a, Q::Response> {
+        let context = query.context_mut().get_or_insert_with(Context::default);
+        if let Some(defaults) = &self.config.druid_context {
+            context.apply_defaults(defaults);
+        }
+        let query_id = context
+            .query_id
+            .get_or_insert_with(|| Uuid::new_v4().to_string())
+            .clone();
+
+        let sent = Arc::new(AtomicBool::new(false));
+        let future = {
+            let sent = sent.clone();
+            let query_id = query_id.clone();
+            async move {
+                self.adapt(&mut query).await?;
+                self.validate(&query).await?;
+
+                let payload = serde_json::to_string(&query)?;
+                sent.store(true, Ordering::Release);
+                let body = self
+                    .post(DruidEndpoint::native, Some(&query_id), payload)
+                    .await?;
+                Ok(query.parse_response(&body)?)
+            }
+        };
+        QueryHandle::new(query_id, self, sent, Box::pin(future))
+    }
+
+    // Scan results are yielded batch by batch as the body arrives instead of being buffered.
+    // Gets a queryId like submit, dropping the stream before its end cancels the query.
+    pub async fn query_stream(
+        &self,
+        mut query: ScanQuery,
+    ) -> Result<ScanStream, CathbadClientError> {
+        // Defaults go in first so that version adaptation sees them, as in submit
+        let context = query.context.get_or_insert_with(Context::default);
+        if let Some(defaults) = &self.config.druid_context {
+            context.apply_defaults(defaults);
+        }
+        let query_id = context
+            .query_id
+            .get_or_insert_with(|| Uuid::new_v4().to_string())
+            .clone();
+        self.adapt(&mut query).await?;
+        self.validate(&query).await?;
+
+        let payload = serde_json::to_string(&query)?;
+        let resp = self
+            .execute(Some(&query_id), |endpoint| {
+                self.client.post(endpoint.native()).body(payload.clone())
+            })
+            .await?;
+        Ok(ScanStream::new(resp.bytes_stream()).with_cancellation(self.clone(), query_id))
+    }
+
+    async fn post(
+        &self,
+        path: fn(&DruidEndpoint) -> Url,
+        query_id: Option<&str>,
+        payload: String,
+    ) -> Result<String, CathbadClientError> {
+        let resp = self
+            .execute(query_id, |endpoint| {
+                self.client.post(path(endpoint)).body(payload.clone())
+            })
+            .await?;
+        Ok(resp.text().await?)
+    }
+
+    pub(crate) async fn get_json<T: DeserializeOwned>(
+        &self,
+        path: impl Fn(&DruidEndpoint) -> Url,
+    ) -> Result<T, CathbadClientError> {
+        let resp = self
+            .execute(None, |endpoint| self.client.get(path(endpoint)))
+            .await?;
+        Ok(serde_json::from_str(&resp.text().await?)?)
+    }
+
+    async fn authorize(
+        &self,
+        builder: RequestBuilder,
+    ) -> Result<RequestBuilder, CathbadClientError> {
+        if let Some(provider) = &self.token_provider {
+            return Ok(builder.bearer_auth(provider.token().await?.expose()));
+        }
+        if let Some(token) = &self.config.druid_bearer_token {
+            return Ok(builder.bearer_auth(token.expose()));
+        }
+        if let Some(username) = &self.config.druid_username {
+            let password = self.config.druid_password.as_ref().map(|p| p.expose());
+            return Ok(builder.basic_auth(username, password));
         }
+        Ok(builder)
     }
-    fn format_endpoint(&self) -> String {
-        format!("{}:{}", self.config.druid_endpoint, self.config.druid_port)
+
+    // Node failures move on to the next node straight away, the retry policy only applies once
+    // every node has been tried. A native query timed out on our side may still run on its node,
+    // it is cancelled there before the same queryId is sent again.
+    async fn execute(
+        &self,
+        query_id: Option<&str>,
+        request: impl Fn(&DruidEndpoint) -> RequestBuilder,
+    ) -> Result<Response, CathbadClientError> {
+        self.cluster.start_probing(&self.client);
+        let mut attempt = 1;
+        let mut tried = vec![];
+        loop {
+            let index = self.cluster.select(&tried);
+            tried.push(index);
+            let node = &self.cluster.nodes()[index];
+            let result = {
+                let _in_flight = node.begin();
+                self.execute_once(request(&node.endpoint)).await
+            };
+            let error = match result {
+                Ok(resp) => {
+                    node.record_success();
+                    return Ok(resp);
+                }
+                Err(error) => error,
+            };
+
+            if error.is_node_failure() {
+                node.record_failure(&self.cluster.policy);
+                if tried.len() < self.cluster.nodes().len() {
+                    continue;
+                }
+            } else {
+                node.record_success();
+            }
+            if attempt < self.retry_policy.max_attempts && error.is_retryable() {
+                if let Some(query_id) = query_id
+                    && let CathbadClientError::Reqwest { reqwest_error } = &error
+                    && reqwest_error.is_timeout()
+                {
+                    let cancel = self.client.delete(node.endpoint.cancel(query_id));
+                    let _ = self.execute_once(cancel).await;
+                }
+                let backoff = self.retry_policy.backoff(attempt, error.retry_after());
+                tokio::time::sleep(backoff).await;
+                tried.clear();
+                attempt += 1;
+            } else {
+                return Err(error);
+            }
+        }
+    }
+
+    async fn execute_once(&self, builder: RequestBuilder) -> Result<Response, CathbadClientError> {
+        let retry = builder.try_clone();
+        let mut resp = self
+            .client
+            .execute(self.authorize(builder).await?.build()?)
+            .await?;
+        if resp.status() == StatusCode::UNAUTHORIZED
+            && let Some(provider) = &self.token_provider
+            && let Some(retry) = retry
+        {
+            provider.refresh().await?;
+            resp = self
+                .client
+                .execute(self.authorize(retry).await?.build()?)
+                .await?;
+        }
+        if !resp.status().is_success() {
+            let status = resp.status();
+            // Only the delay-seconds form of Retry-After is supported
+            let retry_after = resp
+                .headers()
+                .get(RETRY_AFTER)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.trim().parse().ok())
+                .map(Duration::from_secs);
+            let body = resp.text().await?;
+            return Err(CathbadClientError::from_response(status, retry_after, body));
+        }
+        Ok(resp)
+    }
+}
+
+impl Debug for CathbadClient {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CathbadClient")
+            .field("config", &self.config)
+            .field("cluster", &self.cluster.status())
+            .field("token_provider", &self.token_provider.is_some())
+            .field("retry_policy", &self.retry_policy)
+            .field("column_check", &self.column_check)
+            .finish_non_exhaustive()
     }
 }
 
 impl Default for CathbadClient {
     fn default() -> Self {
         let default_config = CathbadClientConfig::default();
-        Self::new(default_config)
+        Self::new(default_config).unwrap()
     }
 }
 
 impl DruidClient for CathbadClient {
-    async fn query(
+    async fn query<Q: TypeConstrainedQuery>(
         &self,
-        query: impl TypeConstrainedQuery,
-    ) -> Result<DruidQueryResponse, CathbadClientError> {
-        if !query.validate_type() || !query.validate_subcomponents() {
-            return Err(CathbadClientError::InvalidQuery);
-        }
+        query: Q,
+    ) -> Result<Q::Response, CathbadClientError> {
+        self.submit(query).await
+    }
 
-        let endpoint = self.format_endpoint();
+    async fn sql(&self, mut query: SqlQuery) -> Result<SqlResponse, CathbadClientError> {
+        if let Some(defaults) = &self.config.druid_context {
+            let context = query.context.get_or_insert_with(SqlContext::default);
+            context.native.apply_defaults(defaults);
+        }
         let payload = serde_json::to_string(&query)?;
-        let req = self.client.post(endpoint).body(payload).build()?;
-        let resp = self.client.execute(req).await?;
-        if !resp.status().is_success() {
-            return match resp.status().as_u16() {
-                400 | 429 | 500 | 501 | 504 => {
-                    let druid_resp: DruidQueryResponse = serde_json::from_str(&resp.text().await?)?;
-                    if druid_resp == (DruidQueryResponse::Success {}) {
-                        return Err(CathbadClientError::DruidErrorUnmarshal);
-                    }
-                    Ok(druid_resp)
-                }
-                _ => Err(CathbadClientError::DruidErrorUnmarshal),
-            };
+        let body = self.post(DruidEndpoint::sql, None, payload).await?;
+        let result_format = query.result_format.unwrap_or_default();
+        Ok(SqlResponse::from_response(&result_format, &body)?)
+    }
+
+    async fn cancel(&self, query_id: &str) -> Result<(), CathbadClientError> {
+        // Brokers only know the queries they run themselves, so every node is asked
+        let cancellations = self
+            .cluster
+            .nodes()
+            .iter()
+            .map(|node| self.execute_once(self.client.delete(node.endpoint.cancel(query_id))));
+        let mut results = join_all(cancellations).await;
+        if results.iter().any(Result::is_ok) {
+            return Ok(());
         }
-        Ok(DruidQueryResponse::Success {}) // you didn't want that data, did you? >;-P
+        results.remove(0).map(|_| ())
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::client::CathbadClient;
+    use crate::client::{
+        CathbadClient, CathbadClientConfig, CathbadClientError, DruidClient, TargetVersion,
+    };
+    use crate::query::{
+        Context, DataSource, DatasourceMetadataQuery, DruidVersion, Filter, TimeBoundaryQuery,
+    };
+    use crate::testing::{FakeDruid, FakeResponse, FakeRoute};
+    use serde_json::json;
+    use std::time::Duration;
 
     #[test]
     fn test_default_client_creation() {
         let client = CathbadClient::default();
 
-        assert_eq!(client.config.druid_endpoint, "http://localhost");
-        assert_eq!(client.config.druid_port, 8888);
+        assert_eq!(client.config.druid_endpoint, "http://localhost:8888");
+        assert_eq!(client.config.druid_port, None);
+
+        let endpoint = client.endpoints().next().unwrap();
+        assert_eq!(
+            endpoint.native().as_str(),
+            "http://localhost:8888/druid/v2/"
+        );
+        assert_eq!(
+            endpoint.sql().as_str(),
+            "http://localhost:8888/druid/v2/sql"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_submit_query_id() {
+        let fake = FakeDruid::start().await.unwrap();
+        let client = CathbadClient::new(fake.config()).unwrap();
+        let mut query = DatasourceMetadataQuery {
+            data_source: DataSource::String("example".to_string()),
+            context: None,
+        };
+
+        let handle = client.submit(query.clone());
+        assert_eq!(handle.query_id().len(), 36);
+
+        query.context = Some(Context {
+            query_id: Some("dashboard-42".to_string()),
+            ..Default::default()
+        });
+        let handle = client.submit(query.clone());
+        assert_eq!(handle.query_id(), "dashboard-42");
+
+        // Handles dropped before their first poll never sent anything to cancel
+        drop(handle);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(fake.requests().is_empty());
+
+        fake.respond(
+            FakeRoute::Native,
+            FakeResponse::json(json!([])).with_delay(Duration::from_secs(5)),
+        );
+        let mut handle = Box::pin(client.submit(query));
+        assert!(
+            tokio::time::timeout(Duration::from_millis(100), handle.as_mut())
+                .await
+                .is_err()
+        );
+        drop(handle);
+        for _ in 0..100 {
+            if fake.requests().len() == 2 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let requests = fake.requests();
+        assert_eq!(requests[1].method, "DELETE");
+        assert_eq!(requests[1].path, "/druid/v2/dashboard-42");
+    }
+
+    #[tokio::test]
+    async fn test_native_endpoint() {
+        let fake = FakeDruid::start().await.unwrap();
+        let client = CathbadClient::new(CathbadClientConfig {
+            druid_pretty: true,
+            ..fake.config()
+        })
+        .unwrap();
+        let query = DatasourceMetadataQuery {
+            data_source: DataSource::String("example".to_string()),
+            context: None,
+        };
+        assert!(client.query(query).await.unwrap().is_empty());
+        assert_eq!(fake.requests()[0].path, "/druid/v2/?pretty");
+
+        let client = CathbadClient::new(CathbadClientConfig {
+            druid_context: Some(Context {
+                priority: Some(10),
+                timeout: Some(60000),
+                ..Default::default()
+            }),
+            ..fake.config()
+        })
+        .unwrap();
+        let query = DatasourceMetadataQuery {
+            data_source: DataSource::String("example".to_string()),
+            context: Some(Context {
+                timeout: Some(5000),
+                ..Default::default()
+            }),
+        };
+        client.query(query).await.unwrap();
+        let sent = fake.requests()[1].json().unwrap();
+        assert_eq!(sent["context"]["priority"], 10);
+        assert_eq!(sent["context"]["timeout"], 5000);
+
+        let invalid = CathbadClientConfig {
+            druid_endpoint: "localhost:8888".to_string(),
+            ..Default::default()
+        };
+        assert!(CathbadClient::new(invalid).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_target_version() {
+        let fake = FakeDruid::start().await.unwrap();
+        fake.respond(
+            FakeRoute::Status,
+            FakeResponse::json(json!({"version": "0.22.1", "modules": []})),
+        );
+        let client = CathbadClient::new(CathbadClientConfig {
+            druid_version: Some(TargetVersion::Auto),
+            ..fake.config()
+        })
+        .unwrap();
+        let query = TimeBoundaryQuery {
+            data_source: DataSource::String("wikipedia".to_string()),
+            bound: None,
+            filter: Some(Filter::Null {
+                column: "user".to_string(),
+            }),
+            context: None,
+        };
+
+        client.query(query.clone()).await.unwrap();
+        client.query(query.clone()).await.unwrap();
+        assert_eq!(
+            client.target_version().await.unwrap(),
+            Some(DruidVersion::new(0, 22, 1))
+        );
+        let requests = fake.requests();
+        assert_eq!(requests.iter().filter(|r| r.path == "/status").count(), 1);
+        assert_eq!(requests[1].json().unwrap()["filter"]["type"], "selector");
+
+        let client = CathbadClient::new(CathbadClientConfig {
+            druid_version: Some(TargetVersion::Fixed(DruidVersion::new(28, 0, 0))),
+            ..fake.config()
+        })
+        .unwrap();
+        let mut query = query;
+        query.context = Some(Context {
+            group_by_strategy: Some("v1".to_string()),
+            ..Default::default()
+        });
+        assert!(matches!(
+            client.query(query.clone()).await,
+            Err(CathbadClientError::Unsupported { .. })
+        ));
+        assert_eq!(fake.requests().len(), 3);
+
+        // JavaScript only goes to clusters that enable it
+        query.context = None;
+        query.filter = Some(Filter::Not {
+            field: Box::new(Filter::Javascript {
+                dimension: "user".to_string(),
+                function: "function(x) { return x == 'bot' }".to_string(),
+            }),
+        });
+        let error = client.query(query.clone()).await.unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "JavaScript is not available in Druid 28.0.0: disabled unless the cluster sets druid.javascript.enabled"
+        );
+        let client = CathbadClient::new(CathbadClientConfig {
+            druid_version: Some(TargetVersion::Fixed(DruidVersion::new(28, 0, 0))),
+            druid_javascript: true,
+            ..fake.config()
+        })
+        .unwrap();
+        client.query(query).await.unwrap();
+        assert_eq!(fake.requests().len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_column_check() {
+        let fake = FakeDruid::start().await.unwrap();
+        let schema = json!({"dimensions": ["page", "user"], "metrics": ["added"]});
+        fake.respond(FakeRoute::Datasources, FakeResponse::json(schema.clone()))
+            .respond(FakeRoute::Datasources, FakeResponse::json(schema));
+        let client = CathbadClient::new(fake.config())
+            .unwrap()
+            .with_column_check();
+        let query = |data_source: serde_json::Value, dimension: &str| -> TimeBoundaryQuery {
+            serde_json::from_value(json!({
+                "queryType": "timeBoundary",
+                "dataSource": data_source,
+                "filter": {"type": "selector", "dimension": dimension, "value": "x"}
+            }))
+            .unwrap()
+        };
+
+        client
+            .query(query(json!("wikipedia"), "user"))
+            .await
+            .unwrap();
+        let error = client
+            .query(query(json!("wikipedia"), "browser"))
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            &error,
+            CathbadClientError::UnresolvedColumns { columns } if columns == &["browser"]
+        ));
+        assert_eq!(error.to_string(), "query reads unknown columns: browser");
+        let paths: Vec<String> = fake.requests().into_iter().map(|r| r.path).collect();
+        assert_eq!(
+            paths,
+            [
+                "/druid/v2/datasources/wikipedia",
+                "/druid/v2/",
+                "/druid/v2/datasources/wikipedia"
+            ]
+        );
 
-        assert_eq!(client.format_endpoint(), "http://localhost:8888");
+        // Inline data sources are checked without the lookup
+        let client = CathbadClient::new(fake.config()).unwrap();
+        let inline = json!(DataSource::Inline {
+            column_names: vec!["page".to_string()],
+            rows: vec![vec!["Main".to_string()]],
+        });
+        client.query(query(inline.clone(), "page")).await.unwrap();
+        assert!(matches!(
+            client.query(query(inline, "user")).await,
+            Err(CathbadClientError::UnresolvedColumns { .. })
+        ));
+        assert_eq!(fake.requests().len(), 4);
     }
 }
@@ -0,0 +1,132 @@
+use crate::client::{CathbadClientError, DruidError};
+use reqwest::StatusCode;
+use std::time::Duration;
+
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    // Including the first attempt, 1 disables retries
+    pub max_attempts: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    pub multiplier: f64,
+    // Full jitter, i.e. a random delay between zero and the exponential backoff
+    pub jitter: bool,
+}
+
+impl RetryPolicy {
+    pub fn none() -> Self {
+        Self {
+            max_attempts: 1,
+            ..Default::default()
+        }
+    }
+
+    // A Retry-After sent by the broker wins over the computed backoff, but is capped just the same
+    pub fn backoff(&self, attempt: u32, retry_after: Option<Duration>) -> Duration {
+        if let Some(retry_after) = retry_after {
+            return retry_after.min(self.max_backoff);
+        }
+
+        let exponent = attempt.saturating_sub(1) as i32;
+        let backoff = self.initial_backoff.as_secs_f64() * self.multiplier.powi(exponent);
+        let backoff = backoff.min(self.max_backoff.as_secs_f64());
+        if self.jitter {
+            Duration::from_secs_f64(rand::random_range(0.0..=backoff))
+        } else {
+            Duration::from_secs_f64(backoff)
+        }
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(200),
+            max_backoff: Duration::from_secs(10),
+            multiplier: 2.0,
+            jitter: true,
+        }
+    }
+}
+
+impl CathbadClientError {
+    // Only failures where the same query can succeed later, a malformed or rejected query never will
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            CathbadClientError::Druid { druid_error, .. } => matches!(
+                druid_error,
+                DruidError::QueryCapacityExceeded
+                    | DruidError::QueryTimeout
+                    | DruidError::QueryInterrupted
+            ),
+            // Proxies and load balancers in front of the broker
+            CathbadClientError::DruidErrorUnmarshal { status, .. } => matches!(
+                *status,
+                StatusCode::BAD_GATEWAY
+                    | StatusCode::SERVICE_UNAVAILABLE
+                    | StatusCode::GATEWAY_TIMEOUT
+            ),
+            CathbadClientError::Reqwest { reqwest_error } => {
+                reqwest_error.is_connect() || reqwest_error.is_timeout()
+            }
+            _ => false,
+        }
+    }
+
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            CathbadClientError::Druid { retry_after, .. }
+            | CathbadClientError::DruidErrorUnmarshal { retry_after, .. } => *retry_after,
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff() {
+        let policy = RetryPolicy {
+            jitter: false,
+            ..Default::default()
+        };
+        assert_eq!(policy.backoff(1, None), Duration::from_millis(200));
+        assert_eq!(policy.backoff(3, None), Duration::from_millis(800));
+        assert_eq!(policy.backoff(20, None), Duration::from_secs(10));
+        assert_eq!(
+            policy.backoff(1, Some(Duration::from_secs(3))),
+            Duration::from_secs(3)
+        );
+        assert_eq!(
+            policy.backoff(1, Some(Duration::from_secs(3600))),
+            Duration::from_secs(10)
+        );
+
+        let jittered = RetryPolicy::default().backoff(3, None);
+        assert!(jittered <= Duration::from_millis(800));
+    }
+
+    #[test]
+    fn test_retryable_errors() {
+        let retryable = |druid_error| CathbadClientError::from(druid_error).is_retryable();
+        assert!(retryable(DruidError::QueryCapacityExceeded));
+        assert!(retryable(DruidError::QueryTimeout));
+        assert!(!retryable(DruidError::SQLParseFailed));
+        assert!(!retryable(DruidError::PlanValidationFailed));
+        assert!(!CathbadClientError::InvalidQuery.is_retryable());
+    }
+
+    #[test]
+    fn test_proxy_retry_after() {
+        let error = CathbadClientError::from_response(
+            StatusCode::SERVICE_UNAVAILABLE,
+            Some(Duration::from_secs(5)),
+            "<html>Service Unavailable</html>".to_string(),
+        );
+        assert!(error.is_retryable());
+        assert_eq!(error.retry_after(), Some(Duration::from_secs(5)));
+    }
+}
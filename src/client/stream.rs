@@ -0,0 +1,371 @@
+use crate::client::handle::cancel_in_background;
+use crate::client::{CathbadClient, CathbadClientError};
+use crate::query::ScanBatch;
+use bytes::Bytes;
+use futures_util::Stream;
+use std::pin::Pin;
+use std::task::{Context, Poll, ready};
+
+// Scan responses are a single JSON array of batches (one per segment, or per batchSize events).
+// ScanStream only pulls the next chunk off the HTTP body once the previous batches have been
+// consumed, so a slow consumer holds back the connection rather than growing a buffer.
+// Dropping the stream before the end cancels the query on the broker.
+pub struct ScanStream {
+    body: Pin<Box<dyn Stream<Item = reqwest::Result<Bytes>> + Send>>,
+    elements: JsonArrayElements,
+    // The body has been read to its end, there is nothing left to cancel
+    body_read: bool,
+    // No more items, after the end of the array or the first error
+    terminated: bool,
+    cancellation: Option<(CathbadClient, String)>,
+}
+
+impl ScanStream {
+    pub(crate) fn new(body: impl Stream<Item = reqwest::Result<Bytes>> + Send + 'static) -> Self {
+        Self {
+            body: Box::pin(body),
+            elements: JsonArrayElements::default(),
+            body_read: false,
+            terminated: false,
+            cancellation: None,
+        }
+    }
+
+    pub(crate) fn with_cancellation(mut self, client: CathbadClient, query_id: String) -> Self {
+        self.cancellation = Some((client, query_id));
+        self
+    }
+
+    // Set for streams from CathbadClient::query_stream
+    pub fn query_id(&self) -> Option<&str> {
+        self.cancellation
+            .as_ref()
+            .map(|(_, query_id)| query_id.as_str())
+    }
+}
+
+impl Drop for ScanStream {
+    fn drop(&mut self) {
+        if !self.body_read
+            && let Some((client, query_id)) = self.cancellation.take()
+        {
+            cancel_in_background(client, query_id);
+        }
+    }
+}
+
+impl Stream for ScanStream {
+    type Item = Result<ScanBatch, CathbadClientError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            if self.terminated {
+                return Poll::Ready(None);
+            }
+            match self.elements.next_element() {
+                Ok(Some(element)) => {
+                    return Poll::Ready(Some(serde_json::from_slice(&element).map_err(Into::into)));
+                }
+                Ok(None) => {}
+                Err(error) => {
+                    self.terminated = true;
+                    return Poll::Ready(Some(Err(error)));
+                }
+            }
+            if self.body_read {
+                self.terminated = true;
+                return Poll::Ready(None);
+            }
+
+            match ready!(self.body.as_mut().poll_next(cx)) {
+                Some(Ok(chunk)) => self.elements.extend(&chunk),
+                Some(Err(error)) => {
+                    self.terminated = true;
+                    return Poll::Ready(Some(Err(error.into())));
+                }
+                None => {
+                    self.body_read = true;
+                    // Druid truncates the body when a query fails after the response has started
+                    if !self.elements.is_closed() {
+                        self.terminated = true;
+                        return Poll::Ready(Some(Err(CathbadClientError::MalformedStream)));
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Splits a top-level JSON array into its raw elements without parsing them
+#[derive(Debug, Default)]
+struct JsonArrayElements {
+    buffer: Vec<u8>,
+    cursor: usize,
+    depth: usize,
+    element_start: Option<usize>,
+    in_string: bool,
+    escaped: bool,
+    opened: bool,
+    closed: bool,
+}
+
+impl JsonArrayElements {
+    fn extend(&mut self, chunk: &[u8]) {
+        self.buffer.extend_from_slice(chunk);
+    }
+
+    fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    fn next_element(&mut self) -> Result<Option<Vec<u8>>, CathbadClientError> {
+        while self.cursor < self.buffer.len() {
+            let index = self.cursor;
+            let byte = self.buffer[index];
+            self.cursor += 1;
+
+            if self.in_string {
+                if self.escaped {
+                    self.escaped = false;
+                } else if byte == b'\\' {
+                    self.escaped = true;
+                } else if byte == b'"' {
+                    self.in_string = false;
+                }
+                continue;
+            }
+
+            match byte {
+                b' ' | b'\t' | b'\r' | b'\n' => {}
+                b'[' if !self.opened => self.opened = true,
+                _ if !self.opened || self.closed => {
+                    return Err(CathbadClientError::MalformedStream);
+                }
+                b',' if self.depth == 0 => {}
+                b']' if self.depth == 0 => self.closed = true,
+                b'{' | b'[' => {
+                    if self.depth == 0 {
+                        self.element_start = Some(index);
+                    }
+                    self.depth += 1;
+                }
+                b'}' | b']' if self.depth > 0 => {
+                    self.depth -= 1;
+                    if self.depth == 0
+                        && let Some(start) = self.element_start.take()
+                    {
+                        let element = self.buffer[start..=index].to_vec();
+                        self.buffer.drain(..self.cursor);
+                        self.cursor = 0;
+                        return Ok(Some(element));
+                    }
+                }
+                b'"' if self.depth > 0 => self.in_string = true,
+                // Scan batches are objects, bare values at the top level mean this isn't a scan response
+                _ if self.depth == 0 => return Err(CathbadClientError::MalformedStream),
+                _ => {}
+            }
+        }
+
+        // Only hold on to the element currently being read
+        let consumed = self.element_start.unwrap_or(self.cursor);
+        self.buffer.drain(..consumed);
+        self.cursor -= consumed;
+        if let Some(start) = self.element_start.as_mut() {
+            *start -= consumed;
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::{CathbadClient, CathbadClientConfig, TargetVersion};
+    use crate::query::{Context, DataSource, DruidVersion, ScanEvents, ScanQuery};
+    use crate::testing::{FakeDruid, FakeResponse, FakeRoute};
+    use futures_util::{StreamExt, stream};
+    use reqwest::StatusCode;
+    use std::time::Duration;
+
+    const BODY: &str = r#"[
+        {"segmentId":"s1","columns":["page"],"events":[{"page":"a [tricky] \"page\" }"}]},
+        {"segmentId":"s2","columns":["page"],"events":[["b"],["c"]]}
+    ]"#;
+
+    #[test]
+    fn test_split_byte_by_byte() {
+        let mut elements = JsonArrayElements::default();
+        let mut found = vec![];
+        for byte in BODY.as_bytes() {
+            elements.extend(&[*byte]);
+            while let Some(element) = elements.next_element().unwrap() {
+                found.push(String::from_utf8(element).unwrap());
+            }
+        }
+        assert!(elements.is_closed());
+        assert_eq!(found.len(), 2);
+        assert!(found[0].ends_with(r#""page\" }"}]}"#));
+        assert!(elements.buffer.len() < 8);
+    }
+
+    #[tokio::test]
+    async fn test_scan_stream() {
+        let chunks: Vec<reqwest::Result<Bytes>> = BODY
+            .as_bytes()
+            .chunks(7)
+            .map(|chunk| Ok(Bytes::copy_from_slice(chunk)))
+            .collect();
+        let batches: Vec<_> = ScanStream::new(stream::iter(chunks)).collect().await;
+
+        assert_eq!(batches.len(), 2);
+        let second = batches[1].as_ref().unwrap();
+        assert_eq!(second.segment_id, "s2");
+        assert!(
+            matches!(second.events, ScanEvents::CompactedList(ref events) if events.len() == 2)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_truncated_scan_stream() {
+        let chunks: Vec<reqwest::Result<Bytes>> =
+            vec![Ok(Bytes::from(&BODY[..BODY.find("s2").unwrap()]))];
+        let batches: Vec<_> = ScanStream::new(stream::iter(chunks)).collect().await;
+
+        assert!(batches[0].is_ok());
+        assert!(matches!(
+            batches.last(),
+            Some(Err(CathbadClientError::MalformedStream))
+        ));
+
+        // The stream ends at the first error instead of picking up again after the bad bytes
+        let corrupt = BODY.replacen("},", "}, x,", 1);
+        let chunks: Vec<reqwest::Result<Bytes>> = vec![Ok(Bytes::from(corrupt))];
+        let batches: Vec<_> = ScanStream::new(stream::iter(chunks)).collect().await;
+        assert_eq!(batches.len(), 2);
+        assert!(batches[0].is_ok());
+        assert!(matches!(
+            batches[1],
+            Err(CathbadClientError::MalformedStream)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_scan_stream_over_http() {
+        let fake = FakeDruid::start().await.unwrap();
+        fake.respond(
+            FakeRoute::Native,
+            FakeResponse::new(StatusCode::OK, BODY).chunked(16, Duration::from_millis(1)),
+        );
+
+        let client = CathbadClient::new(fake.config()).unwrap();
+        let query = ScanQuery {
+            data_source: DataSource::String("wikipedia".to_string()),
+            intervals: vec!["2013-01-01/2014-01-01".parse().unwrap()],
+            virtual_columns: None,
+            result_format: None,
+            offset: None,
+            order: None,
+            legacy: None,
+            context: None,
+        };
+        let stream = client.query_stream(query).await.unwrap();
+        let query_id = stream.query_id().unwrap().to_string();
+        let batches: Vec<_> = stream.collect().await;
+        assert_eq!(batches.len(), 2);
+        assert!(batches.iter().all(Result::is_ok));
+
+        let request = &fake.requests()[0];
+        assert_eq!(request.path, "/druid/v2/");
+        assert_eq!(request.json().unwrap()["queryType"], "scan");
+        assert_eq!(request.json().unwrap()["context"]["queryId"], query_id);
+
+        // Context defaults from the config are adapted like the query's own context
+        let client = CathbadClient::new(CathbadClientConfig {
+            druid_version: Some(TargetVersion::Fixed(DruidVersion::new(28, 0, 0))),
+            druid_context: Some(Context {
+                group_by_strategy: Some("v1".to_string()),
+                ..Default::default()
+            }),
+            ..fake.config()
+        })
+        .unwrap();
+        let query = ScanQuery {
+            data_source: DataSource::String("wikipedia".to_string()),
+            intervals: vec!["2013-01-01/2014-01-01".parse().unwrap()],
+            virtual_columns: None,
+            result_format: None,
+            offset: None,
+            order: None,
+            legacy: None,
+            context: None,
+        };
+        assert!(matches!(
+            client.query_stream(query).await,
+            Err(CathbadClientError::Unsupported { .. })
+        ));
+        assert_eq!(fake.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_scan_stream_cancellation() {
+        let fake = FakeDruid::start().await.unwrap();
+        fake.respond(
+            FakeRoute::Native,
+            FakeResponse::new(StatusCode::OK, BODY).chunked(16, Duration::from_millis(200)),
+        );
+        let client = CathbadClient::new(fake.config()).unwrap();
+        let query = ScanQuery {
+            data_source: DataSource::String("wikipedia".to_string()),
+            intervals: vec!["2013-01-01/2014-01-01".parse().unwrap()],
+            virtual_columns: None,
+            result_format: None,
+            offset: None,
+            order: None,
+            legacy: None,
+            context: None,
+        };
+
+        let mut stream = client.query_stream(query.clone()).await.unwrap();
+        let query_id = stream.query_id().unwrap().to_string();
+        assert!(
+            tokio::time::timeout(Duration::from_millis(50), stream.next())
+                .await
+                .is_err()
+        );
+        drop(stream);
+        for _ in 0..100 {
+            if fake.requests().len() == 2 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let requests = fake.requests();
+        assert_eq!(requests[1].method, "DELETE");
+        assert_eq!(requests[1].path, format!("/druid/v2/{query_id}"));
+
+        // A corrupt body ends the stream, but the broker may still be sending the rest of it
+        let corrupt = format!("[x{}", &BODY[1..]);
+        fake.respond(
+            FakeRoute::Native,
+            FakeResponse::new(StatusCode::OK, &corrupt).chunked(16, Duration::from_millis(200)),
+        );
+        let mut stream = client.query_stream(query).await.unwrap();
+        let query_id = stream.query_id().unwrap().to_string();
+        assert!(matches!(
+            stream.next().await,
+            Some(Err(CathbadClientError::MalformedStream))
+        ));
+        assert!(stream.next().await.is_none());
+        drop(stream);
+        for _ in 0..100 {
+            if fake.requests().len() == 4 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let requests = fake.requests();
+        assert_eq!(requests[3].method, "DELETE");
+        assert_eq!(requests[3].path, format!("/druid/v2/{query_id}"));
+    }
+}
@@ -0,0 +1,222 @@
+use crate::client::{CathbadClientConfig, CathbadClientError};
+use reqwest::{Certificate, ClientBuilder, Identity};
+use std::path::Path;
+
+pub(crate) fn configure_tls(
+    mut builder: ClientBuilder,
+    config: &CathbadClientConfig,
+) -> Result<ClientBuilder, CathbadClientError> {
+    if let Some(path) = &config.druid_ca_bundle {
+        for certificate in Certificate::from_pem_bundle(&read(path)?)? {
+            builder = builder.add_root_certificate(certificate);
+        }
+    }
+
+    match (&config.druid_client_cert, &config.druid_client_key) {
+        (Some(cert), Some(key)) => {
+            builder = builder.identity(Identity::from_pkcs8_pem(&read(cert)?, &read(key)?)?);
+        }
+        (None, None) => {}
+        _ => {
+            return Err(CathbadClientError::Configuration {
+                reason: "client certificate and key must be provided together".to_string(),
+            });
+        }
+    }
+
+    if config.druid_tls_insecure {
+        builder = builder.danger_accept_invalid_certs(true);
+    }
+    Ok(builder)
+}
+
+fn read(path: &Path) -> Result<Vec<u8>, CathbadClientError> {
+    std::fs::read(path).map_err(|e| CathbadClientError::Configuration {
+        reason: format!("cannot read {}: {e}", path.display()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::client::{CathbadClient, CathbadClientConfig, DruidClient};
+    use crate::query::{DataSource, DatasourceMetadataQuery};
+    use openssl::ssl::{Ssl, SslAcceptor, SslMethod, SslVerifyMode};
+    use openssl::x509::X509;
+    use rcgen::{BasicConstraints, Certificate, CertificateParams, DnType, IsCa, KeyPair};
+    use std::io::Write;
+    use std::pin::Pin;
+    use tempfile::NamedTempFile;
+    use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+    use tokio::net::TcpListener;
+    use tokio_native_tls::native_tls;
+
+    const BODY: &str = r#"[{"timestamp":"2013-05-09T18:24:00.000Z","result":{"maxIngestedEventTime":"2013-05-09T18:24:09.007Z"}}]"#;
+
+    // Removed when dropped at the end of the test
+    fn write_temp(contents: &str) -> NamedTempFile {
+        let mut file = NamedTempFile::with_prefix("cathbad-").unwrap();
+        file.write_all(contents.as_bytes()).unwrap();
+        file
+    }
+
+    // Self-signed CA plus a "localhost" server certificate and a client certificate it issued
+    struct CertificateChain {
+        ca: String,
+        server_cert: String,
+        server_key: String,
+        client_cert: String,
+        client_key: String,
+    }
+
+    fn certificate_chain() -> CertificateChain {
+        let ca_key = KeyPair::generate().unwrap();
+        let mut ca_params = CertificateParams::new(vec![]).unwrap();
+        ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        ca_params
+            .distinguished_name
+            .push(DnType::CommonName, "cathbad test CA");
+        let ca = ca_params.self_signed(&ca_key).unwrap();
+
+        let issue = |name: &str, ca: &Certificate| {
+            let key = KeyPair::generate().unwrap();
+            let mut params = CertificateParams::new(vec![name.to_string()]).unwrap();
+            params.distinguished_name.push(DnType::CommonName, name);
+            let cert = params.signed_by(&key, ca, &ca_key).unwrap();
+            (cert.pem(), key.serialize_pem())
+        };
+        let (server_cert, server_key) = issue("localhost", &ca);
+        let (client_cert, client_key) = issue("cathbad", &ca);
+        CertificateChain {
+            ca: ca.pem(),
+            server_cert,
+            server_key,
+            client_cert,
+            client_key,
+        }
+    }
+
+    async fn respond(mut stream: impl AsyncRead + AsyncWrite + Unpin) {
+        let mut request = vec![0; 4096];
+        let _ = stream.read(&mut request).await;
+        let response = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{BODY}",
+            BODY.len()
+        );
+        let _ = stream.write_all(response.as_bytes()).await;
+        let _ = stream.shutdown().await;
+    }
+
+    async fn spawn_tls_server(cert: &str, key: &str) -> u16 {
+        let identity = native_tls::Identity::from_pkcs8(cert.as_bytes(), key.as_bytes()).unwrap();
+        let acceptor = tokio_native_tls::TlsAcceptor::from(
+            native_tls::TlsAcceptor::builder(identity).build().unwrap(),
+        );
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                if let Ok(stream) = acceptor.accept(stream).await {
+                    respond(stream).await;
+                }
+            }
+        });
+        port
+    }
+
+    // native-tls can't ask for client certificates, so this one is built on OpenSSL directly
+    async fn spawn_mutual_tls_server(chain: &CertificateChain) -> u16 {
+        let mut builder = SslAcceptor::mozilla_intermediate_v5(SslMethod::tls()).unwrap();
+        builder
+            .set_certificate(&X509::from_pem(chain.server_cert.as_bytes()).unwrap())
+            .unwrap();
+        builder
+            .set_private_key(
+                &openssl::pkey::PKey::private_key_from_pem(chain.server_key.as_bytes()).unwrap(),
+            )
+            .unwrap();
+        builder
+            .cert_store_mut()
+            .add_cert(X509::from_pem(chain.ca.as_bytes()).unwrap())
+            .unwrap();
+        builder.set_verify(SslVerifyMode::PEER | SslVerifyMode::FAIL_IF_NO_PEER_CERT);
+        let acceptor = builder.build();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let ssl = Ssl::new(acceptor.context()).unwrap();
+                let mut stream = tokio_openssl::SslStream::new(ssl, stream).unwrap();
+                if Pin::new(&mut stream).accept().await.is_ok() {
+                    respond(stream).await;
+                }
+            }
+        });
+        port
+    }
+
+    fn query() -> DatasourceMetadataQuery {
+        DatasourceMetadataQuery {
+            data_source: DataSource::String("example".to_string()),
+            context: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_custom_ca_bundle() {
+        let chain = certificate_chain();
+        let port = spawn_tls_server(&chain.server_cert, &chain.server_key).await;
+
+        let mut config = CathbadClientConfig {
+            druid_endpoint: format!("https://localhost:{port}"),
+            ..Default::default()
+        };
+        let untrusting = CathbadClient::new(config.clone()).unwrap();
+        assert!(untrusting.query(query()).await.is_err());
+
+        let ca = write_temp(&chain.ca);
+        config.druid_ca_bundle = Some(ca.path().to_path_buf());
+        let trusting = CathbadClient::new(config.clone()).unwrap();
+        let rows = trusting.query(query()).await.unwrap();
+        assert_eq!(
+            rows[0].result.max_ingested_event_time,
+            "2013-05-09T18:24:09.007Z"
+        );
+
+        config.druid_ca_bundle = None;
+        config.druid_tls_insecure = true;
+        let insecure = CathbadClient::new(config).unwrap();
+        assert!(insecure.query(query()).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_client_identity() {
+        let chain = certificate_chain();
+        let port = spawn_mutual_tls_server(&chain).await;
+        let (ca, cert, key) = (
+            write_temp(&chain.ca),
+            write_temp(&chain.client_cert),
+            write_temp(&chain.client_key),
+        );
+
+        let mut config = CathbadClientConfig {
+            druid_endpoint: format!("https://localhost:{port}"),
+            druid_ca_bundle: Some(ca.path().to_path_buf()),
+            ..Default::default()
+        };
+        let anonymous = CathbadClient::new(config.clone()).unwrap();
+        assert!(anonymous.query(query()).await.is_err());
+
+        config.druid_client_cert = Some(cert.path().to_path_buf());
+        assert!(CathbadClient::new(config.clone()).is_err());
+
+        config.druid_client_key = Some(key.path().to_path_buf());
+        let identified = CathbadClient::new(config).unwrap();
+        let rows = identified.query(query()).await.unwrap();
+        assert_eq!(
+            rows[0].result.max_ingested_event_time,
+            "2013-05-09T18:24:09.007Z"
+        );
+    }
+}
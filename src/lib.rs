@@ -1,2 +1,4 @@
 pub mod client;
 pub mod query;
+#[cfg(any(test, feature = "test-support"))]
+pub mod testing;
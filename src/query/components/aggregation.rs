@@ -1,6 +1,11 @@
-use crate::query::Filter;
 use crate::query::components::model::QueryComponent;
+use crate::query::{
+    DruidVersion, Expression, Filter, FloatingPointNumber, IntegerNumber, SketchAggregation,
+    SketchPostAggregation, StatisticsAggregation, StatisticsPostAggregation, UnsupportedFeature,
+};
 use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::ops::{Add, Div, Mul, Sub};
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(tag = "type", rename_all = "camelCase")]
@@ -73,36 +78,58 @@ pub enum Aggregation {
     DoubleFirst {
         name: String,
         field_name: String,
+        time_column: Option<String>,
     },
 
     #[serde(rename_all = "camelCase")]
     LongFirst {
         name: String,
         field_name: String,
+        time_column: Option<String>,
     },
 
     #[serde(rename_all = "camelCase")]
     FloatFirst {
         name: String,
         field_name: String,
+        time_column: Option<String>,
+    },
+
+    #[serde(rename_all = "camelCase")]
+    StringFirst {
+        name: String,
+        field_name: String,
+        time_column: Option<String>,
+        max_string_bytes: Option<IntegerNumber>,
     },
 
     #[serde(rename_all = "camelCase")]
     DoubleLast {
         name: String,
         field_name: String,
+        time_column: Option<String>,
     },
 
     #[serde(rename_all = "camelCase")]
     LongLast {
         name: String,
         field_name: String,
+        time_column: Option<String>,
     },
 
     #[serde(rename_all = "camelCase")]
     FloatLast {
         name: String,
         field_name: String,
+        time_column: Option<String>,
+    },
+
+    #[serde(rename_all = "camelCase")]
+    StringLast {
+        name: String,
+        field_name: String,
+        time_column: Option<String>,
+        max_string_bytes: Option<IntegerNumber>,
     },
 
     #[serde(rename_all = "camelCase")]
@@ -129,7 +156,44 @@ pub enum Aggregation {
         field_name: String,
     },
 
+    // Approximate distinct count of a column ingested as hyperUnique, or of raw values with
+    // is_input_hyper_unique false
     #[serde(rename_all = "camelCase")]
+    HyperUnique {
+        name: String,
+        field_name: String,
+        is_input_hyper_unique: Option<bool>,
+        round: Option<bool>,
+    },
+
+    // Approximate distinct count of values, or of value combinations with by_row
+    #[serde(rename_all = "camelCase")]
+    Cardinality {
+        name: String,
+        fields: Vec<String>,
+        by_row: Option<bool>,
+        round: Option<bool>,
+    },
+
+    // Folds fields into the accumulator (__acc unless renamed), then combines partial results
+    #[serde(rename_all = "camelCase")]
+    Expression {
+        name: String,
+        fields: Vec<String>,
+        accumulator_identifier: Option<String>,
+        fold: Expression,
+        combine: Option<Expression>,
+        compare: Option<Expression>,
+        finalize: Option<Expression>,
+        initial_value: Expression,
+        initial_combine_value: Option<Expression>,
+        is_null_unless_aggregated: Option<bool>,
+        should_aggregate_null_inputs: Option<bool>,
+        should_combine_aggregate_null_inputs: Option<bool>,
+        max_size_bytes: Option<IntegerNumber>,
+    },
+
+    #[serde(rename = "javascript", rename_all = "camelCase")]
     JavaScript {
         name: String,
         field_names: Vec<String>,
@@ -138,7 +202,9 @@ pub enum Aggregation {
         fn_reset: String,
     },
 
+    // Named after the wrapped aggregator unless given a name of its own
     Filtered {
+        name: Option<String>,
         filter: Filter,
         aggregator: Box<Aggregation>,
     },
@@ -147,6 +213,52 @@ pub enum Aggregation {
         name: String,
         groupings: Vec<String>,
     },
+
+    #[serde(untagged)]
+    Sketch(SketchAggregation),
+
+    #[serde(untagged)]
+    Statistics(StatisticsAggregation),
+}
+
+impl Aggregation {
+    pub fn name(&self) -> &str {
+        match self {
+            Aggregation::Count { name }
+            | Aggregation::DoubleSum { name, .. }
+            | Aggregation::LongSum { name, .. }
+            | Aggregation::FloatSum { name, .. }
+            | Aggregation::DoubleMax { name, .. }
+            | Aggregation::LongMax { name, .. }
+            | Aggregation::FloatMax { name, .. }
+            | Aggregation::DoubleMin { name, .. }
+            | Aggregation::LongMin { name, .. }
+            | Aggregation::FloatMin { name, .. }
+            | Aggregation::DoubleMean { name, .. }
+            | Aggregation::DoubleFirst { name, .. }
+            | Aggregation::LongFirst { name, .. }
+            | Aggregation::FloatFirst { name, .. }
+            | Aggregation::StringFirst { name, .. }
+            | Aggregation::DoubleLast { name, .. }
+            | Aggregation::LongLast { name, .. }
+            | Aggregation::FloatLast { name, .. }
+            | Aggregation::StringLast { name, .. }
+            | Aggregation::DoubleAny { name, .. }
+            | Aggregation::LongAny { name, .. }
+            | Aggregation::FloatAny { name, .. }
+            | Aggregation::StringAny { name, .. }
+            | Aggregation::HyperUnique { name, .. }
+            | Aggregation::Cardinality { name, .. }
+            | Aggregation::Expression { name, .. }
+            | Aggregation::JavaScript { name, .. }
+            | Aggregation::Grouping { name, .. } => name,
+            Aggregation::Filtered {
+                name, aggregator, ..
+            } => name.as_deref().unwrap_or_else(|| aggregator.name()),
+            Aggregation::Sketch(sketch) => sketch.name(),
+            Aggregation::Statistics(statistics) => statistics.name(),
+        }
+    }
 }
 
 impl QueryComponent for Aggregation {
@@ -167,24 +279,165 @@ impl QueryComponent for Aggregation {
             Aggregation::DoubleFirst { .. } => true,
             Aggregation::LongFirst { .. } => true,
             Aggregation::FloatFirst { .. } => true,
+            Aggregation::StringFirst { .. } => true,
             Aggregation::DoubleLast { .. } => true,
             Aggregation::LongLast { .. } => true,
             Aggregation::FloatLast { .. } => true,
+            Aggregation::StringLast { .. } => true,
             Aggregation::DoubleAny { .. } => true,
             Aggregation::LongAny { .. } => true,
             Aggregation::FloatAny { .. } => true,
             Aggregation::StringAny { .. } => true,
+            Aggregation::HyperUnique { .. } => true,
+            Aggregation::Cardinality { fields, .. } => !fields.is_empty(),
+            Aggregation::Expression {
+                fold,
+                combine,
+                compare,
+                finalize,
+                initial_value,
+                initial_combine_value,
+                accumulator_identifier,
+                ..
+            } => {
+                accumulator_identifier
+                    .as_ref()
+                    .is_none_or(|identifier| !identifier.is_empty())
+                    && [Some(fold), Some(initial_value)]
+                        .into_iter()
+                        .chain(
+                            [combine, compare, finalize, initial_combine_value].map(Option::as_ref),
+                        )
+                        .flatten()
+                        .all(Expression::validate_type)
+            }
             Aggregation::JavaScript { .. } => true,
-            Aggregation::Filtered { .. } => true,
+            Aggregation::Filtered {
+                filter, aggregator, ..
+            } => filter.validate_type() && aggregator.validate_type(),
             Aggregation::Grouping { .. } => true,
+            Aggregation::Sketch(sketch) => sketch.validate_type(),
+            Aggregation::Statistics(statistics) => statistics.validate_type(),
+        }
+    }
+
+    fn adapt(&mut self, version: &DruidVersion) -> Result<(), UnsupportedFeature> {
+        match self {
+            Aggregation::Filtered {
+                filter, aggregator, ..
+            } => {
+                filter.adapt(version)?;
+                aggregator.adapt(version)
+            }
+            Aggregation::Sketch(sketch) => sketch.adapt(version),
+            Aggregation::Statistics(statistics) => statistics.adapt(version),
+            _ => Ok(()),
+        }
+    }
+
+    // Groupings refer to dimension output names rather than columns, and expression
+    // aggregators read their fields, the accumulator in fold isn't a column
+    fn columns(&self) -> Vec<&str> {
+        match self {
+            Aggregation::DoubleSum { field_name, .. }
+            | Aggregation::LongSum { field_name, .. }
+            | Aggregation::FloatSum { field_name, .. }
+            | Aggregation::DoubleMax { field_name, .. }
+            | Aggregation::LongMax { field_name, .. }
+            | Aggregation::FloatMax { field_name, .. }
+            | Aggregation::DoubleMin { field_name, .. }
+            | Aggregation::LongMin { field_name, .. }
+            | Aggregation::FloatMin { field_name, .. }
+            | Aggregation::DoubleMean { field_name, .. }
+            | Aggregation::DoubleAny { field_name, .. }
+            | Aggregation::LongAny { field_name, .. }
+            | Aggregation::FloatAny { field_name, .. }
+            | Aggregation::StringAny { field_name, .. }
+            | Aggregation::HyperUnique { field_name, .. } => vec![field_name],
+            Aggregation::DoubleFirst {
+                field_name,
+                time_column,
+                ..
+            }
+            | Aggregation::LongFirst {
+                field_name,
+                time_column,
+                ..
+            }
+            | Aggregation::FloatFirst {
+                field_name,
+                time_column,
+                ..
+            }
+            | Aggregation::StringFirst {
+                field_name,
+                time_column,
+                ..
+            }
+            | Aggregation::DoubleLast {
+                field_name,
+                time_column,
+                ..
+            }
+            | Aggregation::LongLast {
+                field_name,
+                time_column,
+                ..
+            }
+            | Aggregation::FloatLast {
+                field_name,
+                time_column,
+                ..
+            }
+            | Aggregation::StringLast {
+                field_name,
+                time_column,
+                ..
+            } => [Some(field_name), time_column.as_ref()]
+                .into_iter()
+                .flatten()
+                .map(String::as_str)
+                .collect(),
+            Aggregation::Cardinality { fields, .. } | Aggregation::Expression { fields, .. } => {
+                fields.iter().map(String::as_str).collect()
+            }
+            Aggregation::JavaScript { field_names, .. } => {
+                field_names.iter().map(String::as_str).collect()
+            }
+            Aggregation::Filtered {
+                filter, aggregator, ..
+            } => {
+                let mut columns = filter.columns();
+                columns.extend(aggregator.columns());
+                columns
+            }
+            Aggregation::Sketch(sketch) => sketch.columns(),
+            Aggregation::Statistics(statistics) => statistics.columns(),
+            Aggregation::Count { .. } | Aggregation::Grouping { .. } => vec![],
         }
     }
 }
 
-impl QueryComponent for Option<Aggregation> {
+// Output names have to be unique, post aggregations and having specs refer to them
+impl QueryComponent for Option<Vec<Aggregation>> {
     fn validate_type(&self) -> bool {
-        self.clone()
-            .is_none_or(|aggregation| aggregation.validate_type())
+        let mut names = HashSet::new();
+        self.iter().flatten().all(|aggregation| {
+            aggregation.validate_type()
+                && !aggregation.name().is_empty()
+                && names.insert(aggregation.name())
+        })
+    }
+    fn adapt(&mut self, version: &DruidVersion) -> Result<(), UnsupportedFeature> {
+        self.iter_mut()
+            .flatten()
+            .try_for_each(|aggregation| aggregation.adapt(version))
+    }
+    fn columns(&self) -> Vec<&str> {
+        self.iter()
+            .flatten()
+            .flat_map(Aggregation::columns)
+            .collect()
     }
 }
 
@@ -193,6 +446,8 @@ impl QueryComponent for Option<Aggregation> {
 pub enum PostAggregationType {
     Arithmetic,
     Constant,
+    Expression,
+    #[serde(rename = "javascript")]
     JavaScript,
     HyperUniqueCardinality,
 
@@ -207,72 +462,304 @@ pub enum PostAggregationType {
     LongLeast,
 }
 
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, Eq, PartialEq)]
+pub enum ArithmeticFunction {
+    #[serde(rename = "+")]
+    Add,
+    #[serde(rename = "-")]
+    Subtract,
+    #[serde(rename = "*")]
+    Multiply,
+    // Division by zero gives 0
+    #[serde(rename = "/")]
+    Divide,
+    // Division by zero gives Infinity or NaN, like floating point division
+    #[serde(rename = "quotient")]
+    Quotient,
+    #[serde(rename = "pow")]
+    Power,
+}
+
+impl ArithmeticFunction {
+    fn symbol(&self) -> &'static str {
+        match self {
+            ArithmeticFunction::Add => "+",
+            ArithmeticFunction::Subtract => "-",
+            ArithmeticFunction::Multiply => "*",
+            ArithmeticFunction::Divide => "/",
+            ArithmeticFunction::Quotient => "quotient",
+            ArithmeticFunction::Power => "pow",
+        }
+    }
+}
+
+// Results are compared as floating point numbers unless ordered numericFirst, which sorts nulls
+// and NaNs last
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum PostAggregationOrdering {
+    NumericFirst,
+}
+
+// Fields of arithmetic, greatest and least post aggregations are post aggregations themselves,
+// fieldAccess for aggregators, constant for numbers, or further arithmetic. Only the names of
+// the top level ones show up in results.
 #[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(tag = "type", rename_all = "camelCase")]
 pub enum PostAggregation {
     Arithmetic {
         name: String,
         #[serde(rename = "fn")]
-        fn_: String,
-        fields: Vec<String>,
-        ordering: Option<String>,
+        fn_: ArithmeticFunction,
+        fields: Vec<PostAggregation>,
+        ordering: Option<PostAggregationOrdering>,
     },
 
-    FieldAccess {
+    #[serde(rename_all = "camelCase")]
+    FieldAccess { name: String, field_name: String },
+
+    #[serde(rename_all = "camelCase")]
+    FinalizingFieldAccess { name: String, field_name: String },
+
+    Constant {
         name: String,
-        field_name: String,
+        value: FloatingPointNumber,
     },
 
-    FinalizingFieldAccess {
+    // Reads aggregators and other post aggregations by name
+    Expression {
         name: String,
-        field_name: String,
+        expression: Expression,
+        ordering: Option<PostAggregationOrdering>,
     },
 
     DoubleGreatest {
         name: String,
-        fields: Vec<String>,
+        fields: Vec<PostAggregation>,
     },
 
     LongGreatest {
         name: String,
-        fields: Vec<String>,
+        fields: Vec<PostAggregation>,
     },
 
     DoubleLeast {
         name: String,
-        fields: Vec<String>,
+        fields: Vec<PostAggregation>,
     },
 
     LongLeast {
         name: String,
-        fields: Vec<String>,
+        fields: Vec<PostAggregation>,
     },
 
+    #[serde(rename = "javascript", rename_all = "camelCase")]
     JavaScript {
         name: String,
         field_names: Vec<String>,
         function: String,
     },
 
-    HyperUniqueCardinality {
-        name: String,
-        field_name: String,
-    },
+    #[serde(rename_all = "camelCase")]
+    HyperUniqueCardinality { name: String, field_name: String },
+
+    #[serde(untagged)]
+    Sketch(SketchPostAggregation),
+
+    #[serde(untagged)]
+    Statistics(StatisticsPostAggregation),
 }
 
+impl PostAggregation {
+    // Named after the field, (field("added") / field("rows")).with_name("ratio") for example
+    pub fn field(field_name: impl Into<String>) -> Self {
+        let field_name = field_name.into();
+        PostAggregation::FieldAccess {
+            name: field_name.clone(),
+            field_name,
+        }
+    }
+
+    pub fn constant(value: FloatingPointNumber) -> Self {
+        PostAggregation::Constant {
+            name: value.to_string(),
+            value,
+        }
+    }
+
+    pub fn expression(name: impl Into<String>, expression: Expression) -> Self {
+        PostAggregation::Expression {
+            name: name.into(),
+            expression,
+            ordering: None,
+        }
+    }
+
+    pub fn quotient(self, right: impl Into<PostAggregation>) -> Self {
+        self.arithmetic(ArithmeticFunction::Quotient, right.into())
+    }
+
+    pub fn pow(self, right: impl Into<PostAggregation>) -> Self {
+        self.arithmetic(ArithmeticFunction::Power, right.into())
+    }
+
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        *self.name_mut() = name.into();
+        self
+    }
+
+    pub fn with_ordering(mut self, ordering: PostAggregationOrdering) -> Self {
+        if let PostAggregation::Arithmetic {
+            ordering: current, ..
+        }
+        | PostAggregation::Expression {
+            ordering: current, ..
+        } = &mut self
+        {
+            *current = Some(ordering);
+        }
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            PostAggregation::Arithmetic { name, .. }
+            | PostAggregation::FieldAccess { name, .. }
+            | PostAggregation::FinalizingFieldAccess { name, .. }
+            | PostAggregation::Constant { name, .. }
+            | PostAggregation::Expression { name, .. }
+            | PostAggregation::DoubleGreatest { name, .. }
+            | PostAggregation::LongGreatest { name, .. }
+            | PostAggregation::DoubleLeast { name, .. }
+            | PostAggregation::LongLeast { name, .. }
+            | PostAggregation::JavaScript { name, .. }
+            | PostAggregation::HyperUniqueCardinality { name, .. } => name,
+            PostAggregation::Sketch(sketch) => sketch.name(),
+            PostAggregation::Statistics(statistics) => statistics.name(),
+        }
+    }
+
+    fn name_mut(&mut self) -> &mut String {
+        match self {
+            PostAggregation::Arithmetic { name, .. }
+            | PostAggregation::FieldAccess { name, .. }
+            | PostAggregation::FinalizingFieldAccess { name, .. }
+            | PostAggregation::Constant { name, .. }
+            | PostAggregation::Expression { name, .. }
+            | PostAggregation::DoubleGreatest { name, .. }
+            | PostAggregation::LongGreatest { name, .. }
+            | PostAggregation::DoubleLeast { name, .. }
+            | PostAggregation::LongLeast { name, .. }
+            | PostAggregation::JavaScript { name, .. }
+            | PostAggregation::HyperUniqueCardinality { name, .. } => name,
+            PostAggregation::Sketch(sketch) => sketch.name_mut(),
+            PostAggregation::Statistics(statistics) => statistics.name_mut(),
+        }
+    }
+
+    // Druid evaluates fields left to right, so a chain of the same function stays one node
+    fn arithmetic(self, fn_: ArithmeticFunction, right: PostAggregation) -> Self {
+        let label = |operand: &PostAggregation, flattened: bool| match operand {
+            PostAggregation::Arithmetic { name, .. } if !flattened => format!("({name})"),
+            operand => operand.name().to_string(),
+        };
+        let flattened = matches!(
+            self,
+            PostAggregation::Arithmetic { fn_: left_fn, ordering: None, .. } if left_fn == fn_
+        );
+        let name = format!(
+            "{} {} {}",
+            label(&self, flattened),
+            fn_.symbol(),
+            label(&right, false)
+        );
+        let fields = match self {
+            PostAggregation::Arithmetic {
+                fn_: left_fn,
+                mut fields,
+                ordering: None,
+                ..
+            } if left_fn == fn_ => {
+                fields.push(right);
+                fields
+            }
+            left => vec![left, right],
+        };
+        PostAggregation::Arithmetic {
+            name,
+            fn_,
+            fields,
+            ordering: None,
+        }
+    }
+}
+
+impl From<FloatingPointNumber> for PostAggregation {
+    fn from(value: FloatingPointNumber) -> Self {
+        PostAggregation::constant(value)
+    }
+}
+
+impl From<i64> for PostAggregation {
+    fn from(value: i64) -> Self {
+        PostAggregation::constant(value as FloatingPointNumber)
+    }
+}
+
+macro_rules! operator {
+    ($trait:ident, $method:ident, $fn_:ident) => {
+        impl<T: Into<PostAggregation>> $trait<T> for PostAggregation {
+            type Output = PostAggregation;
+
+            fn $method(self, right: T) -> PostAggregation {
+                self.arithmetic(ArithmeticFunction::$fn_, right.into())
+            }
+        }
+    };
+}
+
+operator!(Add, add, Add);
+operator!(Sub, sub, Subtract);
+operator!(Mul, mul, Multiply);
+operator!(Div, div, Divide);
+
 impl QueryComponent for PostAggregation {
     fn validate_type(&self) -> bool {
         match self {
-            // TODO
-            PostAggregation::Arithmetic { .. } => true,
-            PostAggregation::FieldAccess { .. } => true,
-            PostAggregation::FinalizingFieldAccess { .. } => true,
-            PostAggregation::DoubleGreatest { .. } => true,
-            PostAggregation::LongGreatest { .. } => true,
-            PostAggregation::DoubleLeast { .. } => true,
-            PostAggregation::LongLeast { .. } => true,
+            // Druid rejects arithmetic on a single field
+            PostAggregation::Arithmetic { fields, .. } => {
+                fields.len() >= 2 && fields.iter().all(PostAggregation::validate_type)
+            }
+            PostAggregation::FieldAccess { field_name, .. }
+            | PostAggregation::FinalizingFieldAccess { field_name, .. }
+            | PostAggregation::HyperUniqueCardinality { field_name, .. } => !field_name.is_empty(),
+            PostAggregation::Constant { value, .. } => value.is_finite(),
+            PostAggregation::Expression { expression, .. } => expression.validate_type(),
+            PostAggregation::DoubleGreatest { fields, .. }
+            | PostAggregation::LongGreatest { fields, .. }
+            | PostAggregation::DoubleLeast { fields, .. }
+            | PostAggregation::LongLeast { fields, .. } => {
+                !fields.is_empty() && fields.iter().all(PostAggregation::validate_type)
+            }
             PostAggregation::JavaScript { .. } => true,
-            PostAggregation::HyperUniqueCardinality { .. } => true,
+            PostAggregation::Sketch(sketch) => sketch.validate_type(),
+            PostAggregation::Statistics(statistics) => statistics.validate_type(),
+        }
+    }
+
+    fn adapt(&mut self, version: &DruidVersion) -> Result<(), UnsupportedFeature> {
+        match self {
+            PostAggregation::Arithmetic { fields, .. }
+            | PostAggregation::DoubleGreatest { fields, .. }
+            | PostAggregation::LongGreatest { fields, .. }
+            | PostAggregation::DoubleLeast { fields, .. }
+            | PostAggregation::LongLeast { fields, .. } => {
+                fields.iter_mut().try_for_each(|field| field.adapt(version))
+            }
+            PostAggregation::Expression { expression, .. } => expression.adapt(version),
+            PostAggregation::Sketch(sketch) => sketch.adapt(version),
+            PostAggregation::Statistics(statistics) => statistics.adapt(version),
+            _ => Ok(()),
         }
     }
 }
@@ -284,15 +771,153 @@ impl QueryComponent for Option<PostAggregation> {
     }
 }
 
+// Top level names are output columns, so like aggregators they have to be unique
 impl QueryComponent for Option<Vec<PostAggregation>> {
     fn validate_type(&self) -> bool {
-        self.clone().is_none_or(|vector| {
-            for agg in vector {
-                if !agg.validate_type() {
-                    return false;
-                }
-            }
-            true
+        let mut names = HashSet::new();
+        self.iter().flatten().all(|post_aggregation| {
+            post_aggregation.validate_type()
+                && !post_aggregation.name().is_empty()
+                && names.insert(post_aggregation.name())
         })
     }
+    fn adapt(&mut self, version: &DruidVersion) -> Result<(), UnsupportedFeature> {
+        self.iter_mut()
+            .flatten()
+            .try_for_each(|post_aggregation| post_aggregation.adapt(version))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_aggregations() {
+        let aggregations: Option<Vec<Aggregation>> = serde_json::from_value(json!([
+            {"type": "count", "name": "rows"},
+            {"type": "longSum", "name": "added", "fieldName": "added"},
+            {"type": "stringLast", "name": "last_page", "fieldName": "page", "timeColumn": "edited_at", "maxStringBytes": 256},
+            {"type": "hyperUnique", "name": "users", "fieldName": "user_unique"},
+            {"type": "cardinality", "name": "pairs", "fields": ["country", "city"], "byRow": true},
+            {"type": "expression", "name": "total", "fields": ["delta"], "fold": "__acc + delta", "combine": "__acc + total", "initialValue": "0"},
+            {"type": "filtered", "name": "robot_edits", "filter": {"type": "selector", "dimension": "isRobot", "value": "true"}, "aggregator": {"type": "count", "name": "c"}},
+            {"type": "javascript", "name": "js", "fieldNames": ["x"], "fnAggregate": "", "fnCombine": "", "fnReset": ""}
+        ]))
+        .unwrap();
+        assert!(aggregations.validate_type());
+        assert_eq!(
+            aggregations.columns(),
+            [
+                "added",
+                "page",
+                "edited_at",
+                "user_unique",
+                "country",
+                "city",
+                "delta",
+                "isRobot",
+                "x"
+            ]
+        );
+
+        let aggregations = aggregations.unwrap();
+        assert_eq!(aggregations[6].name(), "robot_edits");
+        let json = serde_json::to_value(&aggregations[7]).unwrap();
+        assert_eq!(json["type"], "javascript");
+        let json = serde_json::to_value(&aggregations[5]).unwrap();
+        assert_eq!(json["initialValue"], "0");
+
+        let duplicate = Some(vec![
+            Aggregation::Count {
+                name: "rows".to_string(),
+            },
+            Aggregation::Filtered {
+                name: None,
+                filter: Filter::True,
+                aggregator: Box::new(Aggregation::Count {
+                    name: "rows".to_string(),
+                }),
+            },
+        ]);
+        assert!(!duplicate.validate_type());
+    }
+
+    #[test]
+    fn test_post_aggregations() {
+        let rate = ((PostAggregation::field("added") - PostAggregation::field("deleted") - 1)
+            * 100
+            / PostAggregation::field("rows"))
+        .with_name("net_rate")
+        .with_ordering(PostAggregationOrdering::NumericFirst);
+        assert_eq!(
+            serde_json::to_value(&rate).unwrap(),
+            json!({
+                "type": "arithmetic",
+                "name": "net_rate",
+                "fn": "/",
+                "ordering": "numericFirst",
+                "fields": [
+                    {
+                        "type": "arithmetic",
+                        "name": "(added - deleted - 1) * 100",
+                        "fn": "*",
+                        "ordering": null,
+                        "fields": [
+                            {
+                                "type": "arithmetic",
+                                "name": "added - deleted - 1",
+                                "fn": "-",
+                                "ordering": null,
+                                "fields": [
+                                    {"type": "fieldAccess", "name": "added", "fieldName": "added"},
+                                    {"type": "fieldAccess", "name": "deleted", "fieldName": "deleted"},
+                                    {"type": "constant", "name": "1", "value": 1.0}
+                                ]
+                            },
+                            {"type": "constant", "name": "100", "value": 100.0}
+                        ]
+                    },
+                    {"type": "fieldAccess", "name": "rows", "fieldName": "rows"}
+                ]
+            })
+        );
+
+        let post_aggregations: Option<Vec<PostAggregation>> = serde_json::from_value(json!([
+            {"type": "arithmetic", "name": "avg", "fn": "quotient", "fields": [
+                {"type": "finalizingFieldAccess", "name": "sum", "fieldName": "sum"},
+                {"type": "longGreatest", "name": "rows", "fields": [
+                    {"type": "fieldAccess", "name": "rows", "fieldName": "rows"},
+                    {"type": "constant", "name": "one", "value": 1}
+                ]}
+            ]},
+            {"type": "expression", "name": "ratio", "expression": "avg / 2"},
+            {"type": "javascript", "name": "js", "fieldNames": ["avg"], "function": "function(avg) { return avg; }"}
+        ]))
+        .unwrap();
+        assert!(post_aggregations.validate_type());
+        let post_aggregations = post_aggregations.unwrap();
+        assert!(matches!(
+            &post_aggregations[0],
+            PostAggregation::Arithmetic { fn_: ArithmeticFunction::Quotient, fields, .. } if fields.len() == 2
+        ));
+        assert_eq!(
+            serde_json::to_value(&post_aggregations[2]).unwrap()["type"],
+            "javascript"
+        );
+
+        let single = PostAggregation::Arithmetic {
+            name: "x".to_string(),
+            fn_: ArithmeticFunction::Add,
+            fields: vec![PostAggregation::field("rows")],
+            ordering: None,
+        };
+        assert!(!single.validate_type());
+        let duplicate = Some(vec![
+            PostAggregation::field("rows"),
+            PostAggregation::field("added").pow(2).with_name("rows"),
+        ]);
+        assert!(!duplicate.validate_type());
+    }
 }
@@ -1,62 +1,112 @@
-use crate::query::{FloatingPointNumber, IntegerNumber};
+use crate::query::components::model::QueryComponent;
+use crate::query::{DruidVersion, FloatingPointNumber, IntegerNumber, UnsupportedFeature};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
 #[derive(Default, Debug, Clone, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Context {
-    timeout: Option<IntegerNumber>,
-    priority: Option<IntegerNumber>,
-    lane: Option<IntegerNumber>,
-    query_id: Option<String>,
-    broker_service: Option<String>,
-    use_cache: Option<bool>,
-    populate_cache: Option<bool>,
-    use_result_level_cache: Option<bool>,
-    populate_result_level_cache: Option<bool>,
-    by_segment: Option<bool>,
-    finalize: Option<bool>,
-    max_scatter_gather_bytes: Option<IntegerNumber>,
-    max_queued_bytes: Option<IntegerNumber>,
-    serialize_date_time_as_long: Option<bool>,
-    serialize_date_time_as_long_inner: Option<bool>,
-    enable_parallel_merge: Option<bool>,
-    parallel_merge_parallelism: Option<IntegerNumber>,
-    parallel_merge_initial_yield_rows: Option<IntegerNumber>,
-    parallel_merge_small_batch_rows: Option<IntegerNumber>,
+    pub timeout: Option<IntegerNumber>,
+    pub priority: Option<IntegerNumber>,
+    pub lane: Option<IntegerNumber>,
+    pub query_id: Option<String>,
+    pub broker_service: Option<String>,
+    pub use_cache: Option<bool>,
+    pub populate_cache: Option<bool>,
+    pub use_result_level_cache: Option<bool>,
+    pub populate_result_level_cache: Option<bool>,
+    pub by_segment: Option<bool>,
+    pub finalize: Option<bool>,
+    pub max_scatter_gather_bytes: Option<IntegerNumber>,
+    pub max_queued_bytes: Option<IntegerNumber>,
+    pub serialize_date_time_as_long: Option<bool>,
+    pub serialize_date_time_as_long_inner: Option<bool>,
+    pub enable_parallel_merge: Option<bool>,
+    pub parallel_merge_parallelism: Option<IntegerNumber>,
+    pub parallel_merge_initial_yield_rows: Option<IntegerNumber>,
+    pub parallel_merge_small_batch_rows: Option<IntegerNumber>,
     #[serde(rename = "useFilterCNF")]
-    use_filter_cnf: Option<bool>,
-    secondary_partition_pruning: Option<bool>,
-    enable_join_left_table_scan_direct: Option<bool>,
-    debug: Option<bool>,
+    pub use_filter_cnf: Option<bool>,
+    pub secondary_partition_pruning: Option<bool>,
+    pub enable_join_left_table_scan_direct: Option<bool>,
+    pub debug: Option<bool>,
 
     // Query-Specific Parameters
 
     // TopN
-    min_top_n_threshold: Option<IntegerNumber>,
+    pub min_top_n_threshold: Option<IntegerNumber>,
 
     // Timeseries
-    skip_empty_buckets: Option<bool>,
+    pub skip_empty_buckets: Option<bool>,
 
     // GroupBy
     // global
-    group_by_strategy: Option<String>,
-    group_by_is_single_thread: Option<bool>,
+    pub group_by_strategy: Option<String>,
+    pub group_by_is_single_thread: Option<bool>,
     // v2
-    buffer_grouper_initial_buckets: Option<IntegerNumber>,
-    buffer_grouper_max_load_factor: Option<FloatingPointNumber>,
-    force_hash_aggregation: Option<bool>,
-    intermediate_combine_degree: Option<IntegerNumber>,
-    num_parallel_combine_threads: Option<IntegerNumber>,
-    apply_limit_push_down_to_segment: Option<bool>,
-    sort_by_dims_first: Option<bool>,
-    force_limit_push_down: Option<bool>,
+    pub buffer_grouper_initial_buckets: Option<IntegerNumber>,
+    pub buffer_grouper_max_load_factor: Option<FloatingPointNumber>,
+    pub force_hash_aggregation: Option<bool>,
+    pub intermediate_combine_degree: Option<IntegerNumber>,
+    pub num_parallel_combine_threads: Option<IntegerNumber>,
+    pub apply_limit_push_down_to_segment: Option<bool>,
+    pub sort_by_dims_first: Option<bool>,
+    pub force_limit_push_down: Option<bool>,
     // v1
-    max_intermediate_rows: Option<IntegerNumber>,
-    max_results: Option<IntegerNumber>,
-    use_of_heap: Option<bool>,
+    pub max_intermediate_rows: Option<IntegerNumber>,
+    pub max_results: Option<IntegerNumber>,
+    pub use_of_heap: Option<bool>,
 
     // Timeseries + GroupBy
-    vectorize: Option<bool>,
-    vector_size: Option<IntegerNumber>,
-    vectorize_virtual_columns: Option<bool>,
+    pub vectorize: Option<bool>,
+    pub vector_size: Option<IntegerNumber>,
+    pub vectorize_virtual_columns: Option<bool>,
+}
+
+impl Context {
+    // Fills in every parameter this context leaves unset
+    pub fn apply_defaults(&mut self, defaults: &Context) {
+        let (Ok(Value::Object(mut merged)), Ok(Value::Object(defaults))) =
+            (serde_json::to_value(&*self), serde_json::to_value(defaults))
+        else {
+            return;
+        };
+        for (key, value) in defaults {
+            if merged.get(&key).is_none_or(Value::is_null) {
+                merged.insert(key, value);
+            }
+        }
+        if let Ok(context) = serde_json::from_value(Value::Object(merged)) {
+            *self = context;
+        }
+    }
+}
+
+impl QueryComponent for Context {
+    fn validate_type(&self) -> bool {
+        true
+    }
+
+    fn adapt(&mut self, version: &DruidVersion) -> Result<(), UnsupportedFeature> {
+        if self.group_by_strategy.as_deref() == Some("v1")
+            && *version >= DruidVersion::GROUP_BY_V1_REMOVED
+        {
+            return Err(UnsupportedFeature {
+                feature: "groupBy v1 strategy".to_string(),
+                version: *version,
+                reason: format!("removed in Druid {}", DruidVersion::GROUP_BY_V1_REMOVED),
+            });
+        }
+        Ok(())
+    }
+}
+
+impl QueryComponent for Option<Context> {
+    fn validate_type(&self) -> bool {
+        self.as_ref().is_none_or(|context| context.validate_type())
+    }
+    fn adapt(&mut self, version: &DruidVersion) -> Result<(), UnsupportedFeature> {
+        self.as_mut()
+            .map_or(Ok(()), |context| context.adapt(version))
+    }
 }
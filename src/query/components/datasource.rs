@@ -1,5 +1,7 @@
 use crate::query::components::model::QueryComponent;
-use crate::query::{Expression, NativeQuery, VirtualColumn};
+use crate::query::{
+    DruidVersion, Expression, NativeQuery, TypeConstrainedQuery, UnsupportedFeature, VirtualColumn,
+};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -31,6 +33,7 @@ pub enum DataSource {
         join_type: String,
     },
 
+    #[serde(rename_all = "camelCase")]
     Unnest {
         base: Box<DataSource>,
         virtual_column: VirtualColumn,
@@ -50,8 +53,32 @@ impl QueryComponent for DataSource {
             DataSource::Union { .. } => true,
             DataSource::Inline { .. } => true,
             DataSource::Query { .. } => true,
-            DataSource::Join { .. } => true,
-            DataSource::Unnest { .. } => true,
+            DataSource::Join { condition, .. } => condition.validate_type(),
+            DataSource::Unnest {
+                base,
+                virtual_column,
+                ..
+            } => base.validate_type() && virtual_column.validate_type(),
+        }
+    }
+
+    fn adapt(&mut self, version: &DruidVersion) -> Result<(), UnsupportedFeature> {
+        match self {
+            DataSource::Query { query } => query.adapt(version),
+            DataSource::Join { left, right, .. } => {
+                left.adapt(version)?;
+                right.adapt(version)
+            }
+            DataSource::Unnest {
+                base,
+                virtual_column,
+                ..
+            } => {
+                version.require("unnest data source", DruidVersion::UNNEST)?;
+                virtual_column.adapt(version)?;
+                base.adapt(version)
+            }
+            _ => Ok(()),
         }
     }
 }
@@ -1,5 +1,5 @@
 use crate::query::components::model::QueryComponent;
-use crate::query::{ExtractionFunction, OutputType};
+use crate::query::{DruidVersion, ExtractionFunction, OutputType, UnsupportedFeature};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -48,6 +48,25 @@ impl QueryComponent for DimensionSpec {
             DimensionSpec::PrefixFiltered { .. } => true,
         }
     }
+
+    fn adapt(&mut self, version: &DruidVersion) -> Result<(), UnsupportedFeature> {
+        match self {
+            DimensionSpec::ListFiltered { delegate, .. }
+            | DimensionSpec::RegexFiltered { delegate, .. }
+            | DimensionSpec::PrefixFiltered { delegate, .. } => delegate.adapt(version),
+            DimensionSpec::Default { .. } | DimensionSpec::Extraction { .. } => Ok(()),
+        }
+    }
+
+    fn columns(&self) -> Vec<&str> {
+        match self {
+            DimensionSpec::Default { dimension, .. }
+            | DimensionSpec::Extraction { dimension, .. } => vec![dimension],
+            DimensionSpec::ListFiltered { delegate, .. }
+            | DimensionSpec::RegexFiltered { delegate, .. }
+            | DimensionSpec::PrefixFiltered { delegate, .. } => delegate.columns(),
+        }
+    }
 }
 
 impl QueryComponent for Vec<DimensionSpec> {
@@ -59,4 +78,12 @@ impl QueryComponent for Vec<DimensionSpec> {
         }
         true
     }
+
+    fn adapt(&mut self, version: &DruidVersion) -> Result<(), UnsupportedFeature> {
+        self.iter_mut().try_for_each(|dim| dim.adapt(version))
+    }
+
+    fn columns(&self) -> Vec<&str> {
+        self.iter().flat_map(DimensionSpec::columns).collect()
+    }
 }
@@ -0,0 +1,982 @@
+use crate::query::Period;
+use crate::query::components::model::QueryComponent;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt::{Display, Formatter, Write};
+use std::ops::{Add, Div, Mul, Neg, Not, Rem, Sub};
+use std::str::FromStr;
+
+// Druid native expression, printed to and parsed from the syntax documented at
+// https://druid.apache.org/docs/latest/querying/math-expr
+// Literals and identifiers are quoted when printed, so values from users can go in as they are.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expression {
+    Null,
+    Long(i64),
+    Double(f64),
+    String(String),
+    // ARRAY<LONG>[1, 2] when element_type is set, [1, 2] otherwise
+    Array {
+        element_type: Option<String>,
+        elements: Vec<Expression>,
+    },
+    Identifier(String),
+    Unary {
+        op: UnaryOperator,
+        operand: Box<Expression>,
+    },
+    Binary {
+        op: BinaryOperator,
+        left: Box<Expression>,
+        right: Box<Expression>,
+    },
+    Function {
+        name: String,
+        args: Vec<Expression>,
+    },
+    // Only valid as the argument of apply functions such as map, filter and fold
+    Lambda {
+        params: Vec<String>,
+        body: Box<Expression>,
+    },
+    // Deserialized text the parser doesn't know, e.g. the <LONG>[1, 2] arrays of 0.22. Sent back
+    // verbatim and opaque to validation and column lookups.
+    Raw(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnaryOperator {
+    Negate,
+    Not,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOperator {
+    Power,
+    Multiply,
+    Divide,
+    Modulo,
+    Add,
+    Subtract,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    Equal,
+    NotEqual,
+    And,
+    Or,
+}
+
+impl BinaryOperator {
+    fn symbol(&self) -> &'static str {
+        use BinaryOperator::*;
+        match self {
+            Power => "^",
+            Multiply => "*",
+            Divide => "/",
+            Modulo => "%",
+            Add => "+",
+            Subtract => "-",
+            Less => "<",
+            LessOrEqual => "<=",
+            Greater => ">",
+            GreaterOrEqual => ">=",
+            Equal => "==",
+            NotEqual => "!=",
+            And => "&&",
+            Or => "||",
+        }
+    }
+
+    // Unary operators bind tighter than all of these, Power is the only right-associative one.
+    // && and || share a level as in Druid's grammar, a || b && c is (a || b) && c.
+    fn precedence(&self) -> u8 {
+        use BinaryOperator::*;
+        match self {
+            And | Or => 1,
+            Less | LessOrEqual | Greater | GreaterOrEqual | Equal | NotEqual => 3,
+            Add | Subtract => 4,
+            Multiply | Divide | Modulo => 5,
+            Power => 6,
+        }
+    }
+
+    fn from_symbol(symbol: &str) -> Option<Self> {
+        use BinaryOperator::*;
+        Some(match symbol {
+            "^" => Power,
+            "*" => Multiply,
+            "/" => Divide,
+            "%" => Modulo,
+            "+" => Add,
+            "-" => Subtract,
+            "<" => Less,
+            "<=" => LessOrEqual,
+            ">" => Greater,
+            ">=" => GreaterOrEqual,
+            "==" => Equal,
+            "!=" => NotEqual,
+            "&&" => And,
+            "||" => Or,
+            _ => return None,
+        })
+    }
+}
+
+impl Expression {
+    pub fn column(name: impl Into<String>) -> Self {
+        Expression::Identifier(name.into())
+    }
+
+    pub fn string(value: impl Into<String>) -> Self {
+        Expression::String(value.into())
+    }
+
+    pub fn array(elements: impl IntoIterator<Item = Expression>) -> Self {
+        Expression::Array {
+            element_type: None,
+            elements: elements.into_iter().collect(),
+        }
+    }
+
+    pub fn call(name: impl Into<String>, args: impl IntoIterator<Item = Expression>) -> Self {
+        Expression::Function {
+            name: name.into(),
+            args: args.into_iter().collect(),
+        }
+    }
+
+    pub fn lambda<P: Into<String>>(params: impl IntoIterator<Item = P>, body: Expression) -> Self {
+        Expression::Lambda {
+            params: params.into_iter().map(Into::into).collect(),
+            body: Box::new(body),
+        }
+    }
+
+    fn binary(self, op: BinaryOperator, right: impl Into<Expression>) -> Self {
+        Expression::Binary {
+            op,
+            left: Box::new(self),
+            right: Box::new(right.into()),
+        }
+    }
+
+    pub fn pow(self, right: impl Into<Expression>) -> Self {
+        self.binary(BinaryOperator::Power, right)
+    }
+
+    pub fn less_than(self, right: impl Into<Expression>) -> Self {
+        self.binary(BinaryOperator::Less, right)
+    }
+
+    pub fn less_or_equal(self, right: impl Into<Expression>) -> Self {
+        self.binary(BinaryOperator::LessOrEqual, right)
+    }
+
+    pub fn greater_than(self, right: impl Into<Expression>) -> Self {
+        self.binary(BinaryOperator::Greater, right)
+    }
+
+    pub fn greater_or_equal(self, right: impl Into<Expression>) -> Self {
+        self.binary(BinaryOperator::GreaterOrEqual, right)
+    }
+
+    pub fn equal_to(self, right: impl Into<Expression>) -> Self {
+        self.binary(BinaryOperator::Equal, right)
+    }
+
+    pub fn not_equal_to(self, right: impl Into<Expression>) -> Self {
+        self.binary(BinaryOperator::NotEqual, right)
+    }
+
+    pub fn and(self, right: impl Into<Expression>) -> Self {
+        self.binary(BinaryOperator::And, right)
+    }
+
+    pub fn or(self, right: impl Into<Expression>) -> Self {
+        self.binary(BinaryOperator::Or, right)
+    }
+
+    // timestamp_floor(expr, period, null, timezone), time_zone is an IANA name, UTC when None
+    pub fn timestamp_floor(self, period: Period, time_zone: Option<&str>) -> Self {
+        let mut args = vec![self, Expression::string(period.to_string())];
+        if let Some(time_zone) = time_zone {
+            args.extend([Expression::Null, Expression::string(time_zone)]);
+        }
+        Expression::call("timestamp_floor", args)
+    }
+
+    // Joda pattern such as yyyy-MM-dd
+    pub fn timestamp_format(self, pattern: &str) -> Self {
+        Expression::call("timestamp_format", [self, Expression::string(pattern)])
+    }
+
+    pub fn concat(parts: impl IntoIterator<Item = Expression>) -> Self {
+        Expression::call("concat", parts)
+    }
+
+    pub fn nvl(self, fallback: impl Into<Expression>) -> Self {
+        Expression::call("nvl", [self, fallback.into()])
+    }
+
+    // JSONPath such as $.user.name
+    pub fn json_value(self, path: &str) -> Self {
+        Expression::call("json_value", [self, Expression::string(path)])
+    }
+
+    pub fn lower(self) -> Self {
+        Expression::call("lower", [self])
+    }
+
+    pub fn upper(self) -> Self {
+        Expression::call("upper", [self])
+    }
+
+    // SQL LIKE pattern, % and _ are wildcards
+    pub fn like(self, pattern: &str) -> Self {
+        Expression::call("like", [self, Expression::string(pattern)])
+    }
+
+    // LONG, DOUBLE, STRING or an ARRAY<...> type
+    pub fn cast(self, output_type: &str) -> Self {
+        Expression::call("cast", [self, Expression::string(output_type)])
+    }
+
+    pub fn if_else(self, then: impl Into<Expression>, otherwise: impl Into<Expression>) -> Self {
+        Expression::call("if", [self, then.into(), otherwise.into()])
+    }
+
+    fn precedence(&self) -> u8 {
+        match self {
+            Expression::Binary { op, .. } => op.precedence(),
+            // Negative literals print with a minus and bind like a unary operator
+            Expression::Long(value) if *value < 0 => 7,
+            Expression::Double(value) if value.is_sign_negative() => 7,
+            Expression::Unary { .. } => 7,
+            Expression::Lambda { .. } | Expression::Raw(_) => 0,
+            _ => 8,
+        }
+    }
+}
+
+impl From<i64> for Expression {
+    fn from(value: i64) -> Self {
+        Expression::Long(value)
+    }
+}
+
+impl From<f64> for Expression {
+    fn from(value: f64) -> Self {
+        Expression::Double(value)
+    }
+}
+
+macro_rules! operator {
+    ($trait:ident, $method:ident, $op:ident) => {
+        impl<T: Into<Expression>> $trait<T> for Expression {
+            type Output = Expression;
+
+            fn $method(self, right: T) -> Expression {
+                self.binary(BinaryOperator::$op, right)
+            }
+        }
+    };
+}
+
+operator!(Add, add, Add);
+operator!(Sub, sub, Subtract);
+operator!(Mul, mul, Multiply);
+operator!(Div, div, Divide);
+operator!(Rem, rem, Modulo);
+
+impl Neg for Expression {
+    type Output = Expression;
+
+    fn neg(self) -> Expression {
+        Expression::Unary {
+            op: UnaryOperator::Negate,
+            operand: Box::new(self),
+        }
+    }
+}
+
+impl Not for Expression {
+    type Output = Expression;
+
+    fn not(self) -> Expression {
+        Expression::Unary {
+            op: UnaryOperator::Not,
+            operand: Box::new(self),
+        }
+    }
+}
+
+fn is_bare_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == '$')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$')
+        && !matches!(name, "null" | "NaN" | "Infinity")
+}
+
+// Same escapes for string literals and quoted identifiers, Druid unescapes both like Java does
+fn write_quoted(f: &mut Formatter<'_>, quote: char, value: &str) -> std::fmt::Result {
+    f.write_char(quote)?;
+    for c in value.chars() {
+        match c {
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            c if c == quote => write!(f, "\\{c}")?,
+            c if c.is_control() => write!(f, "\\u{:04x}", c as u32)?,
+            c => f.write_char(c)?,
+        }
+    }
+    f.write_char(quote)
+}
+
+fn write_list(f: &mut Formatter<'_>, items: &[Expression]) -> std::fmt::Result {
+    for (index, item) in items.iter().enumerate() {
+        if index > 0 {
+            f.write_str(", ")?;
+        }
+        write!(f, "{item}")?;
+    }
+    Ok(())
+}
+
+impl Display for Expression {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Expression::Null => f.write_str("null"),
+            Expression::Long(value) => write!(f, "{value}"),
+            Expression::Double(value) if value.is_nan() => f.write_str("NaN"),
+            Expression::Double(value) if value.is_infinite() => f.write_str(if *value > 0.0 {
+                "Infinity"
+            } else {
+                "-Infinity"
+            }),
+            Expression::Double(value) => {
+                // Must not come out looking like a long
+                let printed = format!("{value:?}");
+                match printed.split_once('e') {
+                    Some((mantissa, exponent)) if !mantissa.contains('.') => {
+                        write!(f, "{mantissa}.0e{exponent}")
+                    }
+                    _ => f.write_str(&printed),
+                }
+            }
+            Expression::String(value) => write_quoted(f, '\'', value),
+            Expression::Array {
+                element_type,
+                elements,
+            } => {
+                if let Some(element_type) = element_type {
+                    write!(f, "ARRAY<{element_type}>")?;
+                }
+                f.write_char('[')?;
+                write_list(f, elements)?;
+                f.write_char(']')
+            }
+            Expression::Identifier(name) if is_bare_identifier(name) => f.write_str(name),
+            Expression::Identifier(name) => write_quoted(f, '"', name),
+            Expression::Unary { op, operand } => {
+                f.write_str(match op {
+                    UnaryOperator::Negate => "-",
+                    UnaryOperator::Not => "!",
+                })?;
+                if operand.precedence() < 7 {
+                    write!(f, "({operand})")
+                } else {
+                    write!(f, "{operand}")
+                }
+            }
+            Expression::Binary { op, left, right } => {
+                let precedence = op.precedence();
+                let right_associative = *op == BinaryOperator::Power;
+                let left_parens = left.precedence() < precedence
+                    || (right_associative && left.precedence() == precedence);
+                let right_parens = right.precedence() < precedence
+                    || (!right_associative && right.precedence() == precedence);
+                if left_parens {
+                    write!(f, "({left})")?;
+                } else {
+                    write!(f, "{left}")?;
+                }
+                write!(f, " {} ", op.symbol())?;
+                if right_parens {
+                    write!(f, "({right})")
+                } else {
+                    write!(f, "{right}")
+                }
+            }
+            Expression::Function { name, args } => {
+                write!(f, "{name}(")?;
+                write_list(f, args)?;
+                f.write_char(')')
+            }
+            Expression::Lambda { params, body } => {
+                write!(f, "({}) -> {body}", params.join(", "))
+            }
+            Expression::Raw(text) => f.write_str(text),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(String),
+    Identifier(String),
+    QuotedIdentifier(String),
+    String(String),
+    Symbol(&'static str),
+}
+
+const SYMBOLS: [&str; 21] = [
+    "->", "<=", ">=", "==", "!=", "&&", "||", "(", ")", "[", "]", ",", "+", "-", "*", "/", "%",
+    "^", "<", ">", "!",
+];
+
+fn tokenize(input: &str) -> Result<Vec<(usize, Token)>, String> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = vec![];
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let start = i;
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+        if c.is_ascii_digit() || (c == '.' && chars.get(i + 1).is_some_and(char::is_ascii_digit)) {
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '.') {
+                // Exponent signs belong to the number
+                if matches!(chars[i], 'e' | 'E') && matches!(chars.get(i + 1), Some('-' | '+')) {
+                    i += 1;
+                }
+                i += 1;
+            }
+            tokens.push((start, Token::Number(chars[start..i].iter().collect())));
+        } else if c.is_ascii_alphabetic() || c == '_' || c == '$' {
+            while i < chars.len()
+                && (chars[i].is_ascii_alphanumeric() || chars[i] == '_' || chars[i] == '$')
+            {
+                i += 1;
+            }
+            tokens.push((start, Token::Identifier(chars[start..i].iter().collect())));
+        } else if c == '\'' || c == '"' {
+            let mut value = String::new();
+            i += 1;
+            loop {
+                match chars.get(i) {
+                    None => return Err(format!("unterminated {c} quote at {start}")),
+                    Some(&quote) if quote == c => break,
+                    Some('\\') => {
+                        i += 1;
+                        match chars.get(i) {
+                            Some('n') => value.push('\n'),
+                            Some('r') => value.push('\r'),
+                            Some('t') => value.push('\t'),
+                            Some('b') => value.push('\u{8}'),
+                            Some('f') => value.push('\u{c}'),
+                            Some('u') => {
+                                let hex: String = chars.iter().skip(i + 1).take(4).collect();
+                                let code = u32::from_str_radix(&hex, 16)
+                                    .ok()
+                                    .filter(|_| hex.len() == 4)
+                                    .and_then(char::from_u32)
+                                    .ok_or_else(|| format!("invalid unicode escape at {i}"))?;
+                                value.push(code);
+                                i += 4;
+                            }
+                            Some(&escaped) => value.push(escaped),
+                            None => return Err(format!("unterminated {c} quote at {start}")),
+                        }
+                    }
+                    Some(&other) => value.push(other),
+                }
+                i += 1;
+            }
+            i += 1;
+            tokens.push((
+                start,
+                if c == '\'' {
+                    Token::String(value)
+                } else {
+                    Token::QuotedIdentifier(value)
+                },
+            ));
+        } else {
+            let rest: String = chars[i..chars.len().min(i + 2)].iter().collect();
+            let symbol = SYMBOLS
+                .iter()
+                .find(|symbol| rest.starts_with(**symbol))
+                .ok_or_else(|| format!("unexpected {c:?} at {i}"))?;
+            i += symbol.len();
+            tokens.push((start, Token::Symbol(symbol)));
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    position: usize,
+    end: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position).map(|(_, token)| token)
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<&Token> {
+        self.tokens
+            .get(self.position + offset)
+            .map(|(_, token)| token)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self
+            .tokens
+            .get(self.position)
+            .map(|(_, token)| token.clone());
+        self.position += 1;
+        token
+    }
+
+    fn error(&self, expected: &str) -> String {
+        match self.tokens.get(self.position) {
+            Some((offset, token)) => format!("expected {expected}, found {token:?} at {offset}"),
+            None => format!("expected {expected} at {}", self.end),
+        }
+    }
+
+    fn expect(&mut self, symbol: &'static str) -> Result<(), String> {
+        if self.peek() == Some(&Token::Symbol(symbol)) {
+            self.position += 1;
+            Ok(())
+        } else {
+            Err(self.error(&format!("'{symbol}'")))
+        }
+    }
+
+    fn expression(&mut self, min_precedence: u8) -> Result<Expression, String> {
+        let mut left = self.prefix()?;
+        while let Some(Token::Symbol(symbol)) = self.peek() {
+            let Some(op) = BinaryOperator::from_symbol(symbol) else {
+                break;
+            };
+            let precedence = op.precedence();
+            if precedence < min_precedence {
+                break;
+            }
+            self.position += 1;
+            let right = if op == BinaryOperator::Power {
+                self.expression(precedence)?
+            } else {
+                self.expression(precedence + 1)?
+            };
+            left = left.binary(op, right);
+        }
+        Ok(left)
+    }
+
+    fn prefix(&mut self) -> Result<Expression, String> {
+        match self.peek() {
+            Some(Token::Symbol("-")) => {
+                self.position += 1;
+                // -5 is a literal, as in Druid
+                if let Some(Token::Number(digits)) = self.peek() {
+                    let number = number(&format!("-{digits}"))?;
+                    self.position += 1;
+                    return Ok(number);
+                }
+                Ok(-self.prefix()?)
+            }
+            Some(Token::Symbol("!")) => {
+                self.position += 1;
+                Ok(!self.prefix()?)
+            }
+            _ => self.primary(),
+        }
+    }
+
+    fn is_lambda(&self) -> bool {
+        let mut offset = 1;
+        loop {
+            match (self.peek_at(offset), self.peek_at(offset + 1)) {
+                (Some(Token::Symbol(")")), Some(Token::Symbol("->"))) => return true,
+                (Some(Token::Identifier(_)), Some(Token::Symbol(","))) => offset += 2,
+                (Some(Token::Identifier(_)), Some(Token::Symbol(")"))) => offset += 1,
+                _ => return false,
+            }
+        }
+    }
+
+    fn primary(&mut self) -> Result<Expression, String> {
+        if self.peek() == Some(&Token::Symbol("(")) && self.is_lambda() {
+            return self.lambda();
+        }
+        match self.next() {
+            Some(Token::Number(digits)) => number(&digits),
+            Some(Token::String(value)) => Ok(Expression::String(value)),
+            Some(Token::QuotedIdentifier(name)) => Ok(Expression::Identifier(name)),
+            Some(Token::Symbol("(")) => {
+                let nested = self.expression(0)?;
+                self.expect(")")?;
+                Ok(nested)
+            }
+            Some(Token::Symbol("[")) => Ok(Expression::array(self.list("]")?)),
+            Some(Token::Identifier(name)) => match (name.as_str(), self.peek()) {
+                ("ARRAY" | "array", Some(Token::Symbol("<"))) => {
+                    self.position += 1;
+                    let element_type = self.element_type()?;
+                    self.expect("[")?;
+                    Ok(Expression::Array {
+                        element_type: Some(element_type),
+                        elements: self.list("]")?,
+                    })
+                }
+                (_, Some(Token::Symbol("("))) => {
+                    self.position += 1;
+                    Ok(Expression::call(name, self.list(")")?))
+                }
+                (_, Some(Token::Symbol("->"))) => {
+                    self.position += 1;
+                    Ok(Expression::lambda([name], self.expression(0)?))
+                }
+                ("null", _) => Ok(Expression::Null),
+                ("NaN", _) => Ok(Expression::Double(f64::NAN)),
+                ("Infinity", _) => Ok(Expression::Double(f64::INFINITY)),
+                _ => Ok(Expression::Identifier(name)),
+            },
+            _ => {
+                self.position -= 1;
+                Err(self.error("an expression"))
+            }
+        }
+    }
+
+    // (x, acc) -> body, is_lambda has checked the parameter list
+    fn lambda(&mut self) -> Result<Expression, String> {
+        self.expect("(")?;
+        let mut params = vec![];
+        while let Some(Token::Identifier(name)) = self.peek() {
+            params.push(name.clone());
+            self.position += 1;
+            if self.peek() == Some(&Token::Symbol(",")) {
+                self.position += 1;
+            }
+        }
+        self.expect(")")?;
+        self.expect("->")?;
+        Ok(Expression::lambda(params, self.expression(0)?))
+    }
+
+    // Up to the closing '>' of ARRAY<...>, nested array types included
+    fn element_type(&mut self) -> Result<String, String> {
+        let mut element_type = String::new();
+        let mut depth = 1;
+        loop {
+            match self.next() {
+                Some(Token::Identifier(name)) => element_type.push_str(&name),
+                Some(Token::Symbol("<")) => {
+                    depth += 1;
+                    element_type.push('<');
+                }
+                Some(Token::Symbol(">")) => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Ok(element_type);
+                    }
+                    element_type.push('>');
+                }
+                _ => {
+                    self.position -= 1;
+                    return Err(self.error("an array element type"));
+                }
+            }
+        }
+    }
+
+    fn list(&mut self, close: &'static str) -> Result<Vec<Expression>, String> {
+        let mut items = vec![];
+        if self.peek() == Some(&Token::Symbol(close)) {
+            self.position += 1;
+            return Ok(items);
+        }
+        loop {
+            items.push(self.expression(0)?);
+            match self.next() {
+                Some(Token::Symbol(",")) => {}
+                Some(Token::Symbol(symbol)) if symbol == close => return Ok(items),
+                _ => {
+                    self.position -= 1;
+                    return Err(self.error(&format!("',' or '{close}'")));
+                }
+            }
+        }
+    }
+}
+
+fn number(digits: &str) -> Result<Expression, String> {
+    if digits.contains(['.', 'e', 'E']) {
+        digits
+            .parse()
+            .map(Expression::Double)
+            .map_err(|_| format!("invalid number {digits}"))
+    } else {
+        digits
+            .parse()
+            .map(Expression::Long)
+            .map_err(|_| format!("invalid number {digits}"))
+    }
+}
+
+impl FromStr for Expression {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser {
+            tokens: tokenize(s)?,
+            position: 0,
+            end: s.len(),
+        };
+        let expression = parser.expression(0)?;
+        if parser.peek().is_some() {
+            return Err(parser.error("the end of the expression"));
+        }
+        Ok(expression)
+    }
+}
+
+impl Serialize for Expression {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Expression {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        Ok(text.parse().unwrap_or(Expression::Raw(text)))
+    }
+}
+
+// Names that can't be quoted must be bare identifiers, or they'd change the expression around them
+impl QueryComponent for Expression {
+    fn validate_type(&self) -> bool {
+        match self {
+            Expression::Array {
+                element_type,
+                elements,
+            } => {
+                element_type.as_ref().is_none_or(|element_type| {
+                    element_type
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '<' || c == '>')
+                }) && elements.iter().all(Expression::validate_type)
+            }
+            Expression::Unary { operand, .. } => operand.validate_type(),
+            Expression::Binary { left, right, .. } => left.validate_type() && right.validate_type(),
+            Expression::Function { name, args } => {
+                is_bare_identifier(name) && args.iter().all(Expression::validate_type)
+            }
+            Expression::Lambda { params, body } => {
+                params.iter().all(|param| is_bare_identifier(param)) && body.validate_type()
+            }
+            _ => true,
+        }
+    }
+
+    fn columns(&self) -> Vec<&str> {
+        let mut columns = vec![];
+        collect_identifiers(self, &mut vec![], &mut columns);
+        columns
+    }
+}
+
+// Lambda parameters shadow columns of the same name inside the lambda body
+fn collect_identifiers<'a>(
+    expression: &'a Expression,
+    bound: &mut Vec<&'a str>,
+    columns: &mut Vec<&'a str>,
+) {
+    match expression {
+        Expression::Identifier(name)
+            if !bound.contains(&name.as_str()) && !columns.contains(&name.as_str()) =>
+        {
+            columns.push(name)
+        }
+        Expression::Array { elements, .. } => elements
+            .iter()
+            .for_each(|element| collect_identifiers(element, bound, columns)),
+        Expression::Unary { operand, .. } => collect_identifiers(operand, bound, columns),
+        Expression::Binary { left, right, .. } => {
+            collect_identifiers(left, bound, columns);
+            collect_identifiers(right, bound, columns);
+        }
+        Expression::Function { args, .. } => args
+            .iter()
+            .for_each(|arg| collect_identifiers(arg, bound, columns)),
+        Expression::Lambda { params, body } => {
+            let depth = bound.len();
+            bound.extend(params.iter().map(String::as_str));
+            collect_identifiers(body, bound, columns);
+            bound.truncate(depth);
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builders() {
+        let page = Expression::column("page");
+        let expression = Expression::concat([
+            page.clone().lower(),
+            Expression::string("'; drop"),
+            Expression::column("user name"),
+        ]);
+        assert_eq!(
+            expression.to_string(),
+            r#"concat(lower(page), '\'; drop', "user name")"#
+        );
+
+        let added = Expression::column("added");
+        let expression = ((added.clone() + 1) * 2)
+            .greater_than(added.clone() - (Expression::column("deleted") - 3))
+            .and(!Expression::column("isRobot").or(Expression::Null.equal_to(page)));
+        assert_eq!(
+            expression.to_string(),
+            "(added + 1) * 2 > added - (deleted - 3) && !(isRobot || null == page)"
+        );
+
+        let floor =
+            Expression::column("__time").timestamp_floor(Period::days(1), Some("America/New_York"));
+        assert_eq!(
+            floor.to_string(),
+            "timestamp_floor(__time, 'P1D', null, 'America/New_York')"
+        );
+        assert_eq!(
+            Expression::column("attrs")
+                .json_value("$.os")
+                .nvl(Expression::string("unknown"))
+                .to_string(),
+            "nvl(json_value(attrs, '$.os'), 'unknown')"
+        );
+        assert_eq!(
+            Expression::Long(2)
+                .pow(Expression::Long(3).pow(2))
+                .to_string(),
+            "2 ^ 3 ^ 2"
+        );
+        assert_eq!(
+            Expression::Long(2)
+                .pow(3)
+                .pow(Expression::Double(0.5))
+                .to_string(),
+            "(2 ^ 3) ^ 0.5"
+        );
+        assert_eq!(Expression::Double(1e21).to_string(), "1.0e21");
+
+        assert!(!Expression::call("x) || evil(", []).validate_type());
+    }
+
+    #[test]
+    fn test_parser() {
+        let cases = [
+            "concat(lower(page), '\\'; drop', \"user name\")",
+            "(added + 1) * 2 > added - (deleted - 3) && !(isRobot || null == page)",
+            "timestamp_floor(__time, 'P1D', null, 'America/New_York')",
+            "-2 ^ 2",
+            "2 ^ 3 ^ 2",
+            "-x % 3",
+            "map((x) -> x + 1, ARRAY<LONG>[1, 2, -3])",
+            "fold((x, acc) -> x + acc, [1.5, 0.002], 0)",
+            "if(a == 'b\\nc', 1, 0)",
+        ];
+        for case in cases {
+            let parsed: Expression = case.parse().unwrap();
+            assert_eq!(parsed.to_string(), case);
+        }
+
+        let parsed: Expression = "1 + 2 * 3 - 4".parse().unwrap();
+        assert_eq!(parsed, (Expression::Long(1) + Expression::Long(2) * 3) - 4);
+        let parsed: Expression = "-2 ^ 2".parse().unwrap();
+        assert_eq!(parsed, Expression::Long(-2).pow(2));
+        let (a, b, c) = (
+            Expression::column("a"),
+            Expression::column("b"),
+            Expression::column("c"),
+        );
+        let grouped_right = a.clone().or(b.clone().and(c.clone()));
+        assert_eq!(grouped_right.to_string(), "a || (b && c)");
+        let grouped_left = a.clone().or(b.clone()).and(c.clone());
+        assert_eq!(grouped_left.to_string(), "a || b && c");
+        for expression in [grouped_right, grouped_left] {
+            let parsed: Expression = expression.to_string().parse().unwrap();
+            assert_eq!(parsed, expression);
+        }
+        let parsed: Expression = "a && b || c".parse().unwrap();
+        assert_eq!(parsed, a.and(b).or(c));
+
+        for keyword in ["null", "NaN", "Infinity"] {
+            let column = Expression::column(keyword);
+            assert_eq!(column.to_string(), format!("\"{keyword}\""));
+            assert_eq!(column.to_string().parse::<Expression>().unwrap(), column);
+        }
+        let parsed: Expression = "NULL".parse().unwrap();
+        assert_eq!(parsed, Expression::column("NULL"));
+        assert_eq!(Expression::column("NULL").to_string(), "NULL");
+
+        let parsed: Expression = "x -> x * 2".parse().unwrap();
+        assert_eq!(parsed.to_string(), "(x) -> x * 2");
+        let parsed: Expression = r#""a\"b" == 'é'"#.parse().unwrap();
+        assert_eq!(
+            parsed,
+            Expression::column("a\"b").equal_to(Expression::string("é"))
+        );
+
+        for invalid in [
+            "",
+            "1 +",
+            "f(1,",
+            "'open",
+            "a b",
+            "(1",
+            "a = b",
+            "ARRAY<LONG[1]",
+        ] {
+            assert!(invalid.parse::<Expression>().is_err(), "{invalid}");
+        }
+
+        let parsed: Expression = "map((x) -> x + offset, concat(x, tags, offset))"
+            .parse()
+            .unwrap();
+        assert_eq!(parsed.columns(), ["offset", "x", "tags"]);
+
+        let json = serde_json::to_string(&(Expression::column("a") + 1)).unwrap();
+        assert_eq!(json, r#""a + 1""#);
+
+        // Left as it is rather than failing the whole query
+        let legacy: Expression =
+            serde_json::from_str(r#""array_contains(tags, <LONG>[1,2])""#).unwrap();
+        assert_eq!(
+            legacy,
+            Expression::Raw("array_contains(tags, <LONG>[1,2])".to_string())
+        );
+        assert!(legacy.validate_type());
+        assert!(legacy.columns().is_empty());
+        assert_eq!(
+            serde_json::to_string(&(legacy + 1)).unwrap(),
+            r#""(array_contains(tags, <LONG>[1,2])) + 1""#
+        );
+    }
+}
@@ -1,5 +1,5 @@
 use crate::query::components::model::QueryComponent;
-use crate::query::{Granularity, IntegerNumber, SearchQuery};
+use crate::query::{Granularity, IntegerNumber, SearchQuerySpec};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -16,7 +16,7 @@ pub enum ExtractionFunction {
         expr: String,
     },
     SearchQuery {
-        query: SearchQuery,
+        query: SearchQuerySpec,
     },
     Substring {
         index: IntegerNumber,
@@ -37,6 +37,7 @@ pub enum ExtractionFunction {
         result_format: String,
         joda: Option<bool>,
     },
+    #[serde(rename = "javascript")]
     JavaScript {
         function: String,
         injective: Option<bool>,
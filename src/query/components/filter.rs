@@ -1,13 +1,18 @@
 use crate::query::components::model::QueryComponent;
-use crate::query::{Expression, ExtractionFunction, Interval, SearchQuery, Sort};
+use crate::query::{
+    DruidVersion, Expression, ExtractionFunction, Interval, SearchQuerySpec, Sort,
+    UnsupportedFeature,
+};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(tag = "type", rename_all = "camelCase")]
 pub enum Filter {
+    // A null value matches nulls, and "" too when Druid runs with default value null handling
     Selector {
         dimension: String,
-        value: String,
+        value: Option<String>,
     },
     ColumnComparison {
         dimensions: Vec<String>,
@@ -32,7 +37,7 @@ pub enum Filter {
     #[serde(rename_all = "camelCase")]
     Search {
         dimension: String,
-        query: SearchQuery,
+        query: SearchQuerySpec,
         extraction_function: Option<ExtractionFunction>,
     },
     In {
@@ -66,6 +71,36 @@ pub enum Filter {
     Expression {
         expression: Expression,
     },
+
+    // Typed filters, match values are compared as match_value_type, e.g. "LONG" or "ARRAY<STRING>"
+    #[serde(rename = "equals", rename_all = "camelCase")]
+    Equality {
+        column: String,
+        match_value_type: String,
+        match_value: Value,
+    },
+    Null {
+        column: String,
+    },
+    #[serde(rename_all = "camelCase")]
+    Range {
+        column: String,
+        match_value_type: String,
+        lower: Option<Value>,
+        upper: Option<Value>,
+        lower_open: Option<bool>,
+        upper_open: Option<bool>,
+    },
+}
+
+// Bound and selector filters compare strings
+fn scalar_string(value: &Value) -> Option<String> {
+    match value {
+        Value::String(value) => Some(value.clone()),
+        Value::Number(value) => Some(value.to_string()),
+        Value::Bool(value) => Some(value.to_string()),
+        _ => None,
+    }
 }
 
 impl QueryComponent for Filter {
@@ -84,8 +119,106 @@ impl QueryComponent for Filter {
             Filter::Like { .. } => true,
             Filter::Bound { .. } => true,
             Filter::Interval { .. } => true,
-            Filter::True { .. } => true,
-            Filter::Expression { .. } => true,
+            Filter::True => true,
+            Filter::Expression { expression } => expression.validate_type(),
+            Filter::Equality { .. } => true,
+            Filter::Null { .. } => true,
+            Filter::Range { .. } => true,
+        }
+    }
+
+    // Before typed filters, scalar equality and range filters become selector and bound filters.
+    // Null checks become selector filters on null, which match nulls in either null handling mode.
+    fn adapt(&mut self, version: &DruidVersion) -> Result<(), UnsupportedFeature> {
+        match self {
+            Filter::And { fields } | Filter::Or { fields } => {
+                return fields.iter_mut().try_for_each(|field| field.adapt(version));
+            }
+            Filter::Not { field } => return field.adapt(version),
+            _ => {}
+        }
+        if *version >= DruidVersion::TYPED_FILTERS {
+            return Ok(());
+        }
+
+        let rewritten = match self {
+            Filter::Equality {
+                column,
+                match_value,
+                ..
+            } => scalar_string(match_value).map(|value| Filter::Selector {
+                dimension: column.clone(),
+                value: Some(value),
+            }),
+            Filter::Null { column } => Some(Filter::Selector {
+                dimension: column.clone(),
+                value: None,
+            }),
+            Filter::Range {
+                column,
+                match_value_type,
+                lower,
+                upper,
+                lower_open,
+                upper_open,
+            } => {
+                let lower = lower.as_ref().map(scalar_string);
+                let upper = upper.as_ref().map(scalar_string);
+                let numeric = matches!(match_value_type.as_str(), "LONG" | "FLOAT" | "DOUBLE");
+                match (lower, upper) {
+                    (Some(None), _) | (_, Some(None)) => None,
+                    (lower, upper) => Some(Filter::Bound {
+                        dimension: column.clone(),
+                        lower: lower.flatten(),
+                        upper: upper.flatten(),
+                        lower_strict: *lower_open,
+                        upper_strict: *upper_open,
+                        ordering: Some(if numeric {
+                            Sort::Numeric
+                        } else {
+                            Sort::Lexicographic
+                        }),
+                        extraction_function: None,
+                    }),
+                }
+            }
+            _ => return Ok(()),
+        };
+        match rewritten {
+            Some(filter) => {
+                *self = filter;
+                Ok(())
+            }
+            None => Err(UnsupportedFeature {
+                feature: "typed filter on an array value".to_string(),
+                version: *version,
+                reason: format!("requires Druid {}", DruidVersion::TYPED_FILTERS),
+            }),
+        }
+    }
+
+    fn columns(&self) -> Vec<&str> {
+        match self {
+            Filter::Selector { dimension, .. }
+            | Filter::Regex { dimension, .. }
+            | Filter::Javascript { dimension, .. }
+            | Filter::Search { dimension, .. }
+            | Filter::In { dimension, .. }
+            | Filter::Like { dimension, .. }
+            | Filter::Bound { dimension, .. }
+            | Filter::Interval { dimension, .. } => vec![dimension],
+            Filter::Equality { column, .. }
+            | Filter::Null { column }
+            | Filter::Range { column, .. } => vec![column],
+            Filter::ColumnComparison { dimensions } => {
+                dimensions.iter().map(String::as_str).collect()
+            }
+            Filter::And { fields } | Filter::Or { fields } => {
+                fields.iter().flat_map(|field| field.columns()).collect()
+            }
+            Filter::Not { field } => field.columns(),
+            Filter::Expression { expression } => expression.columns(),
+            Filter::True => vec![],
         }
     }
 }
@@ -94,4 +227,10 @@ impl QueryComponent for Option<Filter> {
     fn validate_type(&self) -> bool {
         self.clone().is_none_or(|filter| filter.validate_type())
     }
+    fn adapt(&mut self, version: &DruidVersion) -> Result<(), UnsupportedFeature> {
+        self.as_mut().map_or(Ok(()), |filter| filter.adapt(version))
+    }
+    fn columns(&self) -> Vec<&str> {
+        self.as_ref().map_or(vec![], Filter::columns)
+    }
 }
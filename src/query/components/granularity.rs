@@ -1,7 +1,13 @@
 use crate::query::components::model::QueryComponent;
-use serde::{Deserialize, Serialize};
+use crate::query::{
+    DruidVersion, IntegerNumber, Interval, Period, UnsupportedFeature, format_time, parse_time,
+};
+use chrono::{DateTime, FixedOffset, TimeDelta, TimeZone};
+use chrono_tz::Tz;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+// Simple granularities are written as plain strings, the others as objects
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 #[serde(rename_all = "snake_case")] // ???
 pub enum Granularity {
     All,
@@ -16,6 +22,246 @@ pub enum Granularity {
     Month,
     Quarter,
     Year,
+    #[serde(untagged)]
+    Period(PeriodGranularity),
+    #[serde(untagged)]
+    Duration(DurationGranularity),
+}
+
+// Buckets of a calendar period in a time zone, e.g. weeks starting on Monday in New York
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "type", rename = "period", rename_all = "camelCase")]
+pub struct PeriodGranularity {
+    pub period: Period,
+    // IANA name such as America/New_York, UTC when unset
+    pub time_zone: Option<String>,
+    #[serde(
+        default,
+        serialize_with = "serialize_origin",
+        deserialize_with = "deserialize_origin"
+    )]
+    pub origin: Option<DateTime<FixedOffset>>,
+}
+
+// Buckets of a fixed length in milliseconds, counted from the origin or the epoch
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "type", rename = "duration", rename_all = "camelCase")]
+pub struct DurationGranularity {
+    pub duration: IntegerNumber,
+    #[serde(
+        default,
+        serialize_with = "serialize_origin",
+        deserialize_with = "deserialize_origin"
+    )]
+    pub origin: Option<DateTime<FixedOffset>>,
+}
+
+impl Granularity {
+    pub fn period(period: Period) -> Self {
+        Granularity::Period(PeriodGranularity {
+            period,
+            time_zone: None,
+            origin: None,
+        })
+    }
+
+    pub fn duration(millis: IntegerNumber) -> Self {
+        Granularity::Duration(DurationGranularity {
+            duration: millis,
+            origin: None,
+        })
+    }
+}
+
+// Enumerating more than this is almost certainly a mistake, e.g. second granularity over years
+const MAX_BUCKETS: usize = 1_000_000;
+
+impl Granularity {
+    // Buckets overlapping the interval as Druid would key them: the first may start before the
+    // interval and the last may end after it. Timeseries rows carry the start of their bucket.
+    pub fn buckets(&self, interval: &Interval) -> Result<Vec<Interval>, String> {
+        let period = |period: Period| PeriodGranularity {
+            period,
+            time_zone: None,
+            origin: None,
+        };
+        let minutes = |minutes| Period {
+            minutes,
+            ..Default::default()
+        };
+        let months = |months| Period {
+            months,
+            ..Default::default()
+        };
+        match self {
+            Granularity::All => Ok(vec![*interval]),
+            Granularity::None => Err("none granularity has a bucket per millisecond".to_string()),
+            Granularity::Second => period(Period {
+                seconds: 1,
+                ..Default::default()
+            })
+            .buckets(interval),
+            Granularity::Minute => period(minutes(1)).buckets(interval),
+            Granularity::FifteenMinute => period(minutes(15)).buckets(interval),
+            Granularity::ThirtyMinute => period(minutes(30)).buckets(interval),
+            Granularity::Hour => period(Period::hours(1)).buckets(interval),
+            Granularity::Day => period(Period::days(1)).buckets(interval),
+            Granularity::Week => period(Period {
+                weeks: 1,
+                ..Default::default()
+            })
+            .buckets(interval),
+            Granularity::Month => period(months(1)).buckets(interval),
+            Granularity::Quarter => period(months(3)).buckets(interval),
+            Granularity::Year => period(months(12)).buckets(interval),
+            Granularity::Period(granularity) => granularity.buckets(interval),
+            Granularity::Duration(granularity) => granularity.buckets(interval),
+        }
+    }
+}
+
+impl PeriodGranularity {
+    fn buckets(&self, interval: &Interval) -> Result<Vec<Interval>, String> {
+        if self.period.is_zero() {
+            return Err("period granularity with an empty period".to_string());
+        }
+        let time_zone: Tz = match &self.time_zone {
+            Some(name) => name
+                .parse()
+                .map_err(|_| format!("unknown time zone {name:?}"))?,
+            None => Tz::UTC,
+        };
+        // Without an origin Druid counts from 1970-01-01T00:00 in the time zone, except that a
+        // plain week starts on Monday
+        let origin = match self.origin {
+            Some(origin) => origin.with_timezone(&time_zone),
+            None => {
+                let weekly = self.period
+                    == Period {
+                        weeks: 1,
+                        ..Default::default()
+                    };
+                time_zone
+                    .with_ymd_and_hms(1970, 1, if weekly { 5 } else { 1 }, 0, 0, 0)
+                    .earliest()
+                    .ok_or_else(|| format!("no midnight on 1970-01-01 in {time_zone}"))?
+            }
+        };
+
+        let elapsed = (interval.start() - origin.fixed_offset()).num_milliseconds();
+        let estimate = (elapsed as f64 / self.period.nominal_millis()).floor() as i64;
+        // Adding the period n times over can drift, e.g. from the 31st, so scale it instead
+        let nth = |n: i64| {
+            let times = u32::try_from(n.unsigned_abs()).ok();
+            let scaled = times.and_then(|times| self.period.times(times));
+            let boundary = match scaled {
+                Some(scaled) if n >= 0 => scaled.add_to(origin),
+                Some(scaled) => scaled.subtract_from(origin),
+                None => None,
+            };
+            boundary
+                .map(|boundary| boundary.fixed_offset())
+                .ok_or_else(|| {
+                    format!(
+                        "bucket {n} of {} is out of range or falls in a time zone transition",
+                        self.period
+                    )
+                })
+        };
+        collect_buckets(interval, estimate, nth)
+    }
+}
+
+impl DurationGranularity {
+    fn buckets(&self, interval: &Interval) -> Result<Vec<Interval>, String> {
+        let duration = i64::try_from(self.duration)
+            .ok()
+            .filter(|duration| *duration > 0)
+            .ok_or_else(|| format!("invalid bucket duration {}", self.duration))?;
+        let origin = self.origin.unwrap_or(DateTime::UNIX_EPOCH.fixed_offset());
+
+        let elapsed = (interval.start() - origin).num_milliseconds();
+        let nth = |n: i64| {
+            n.checked_mul(duration)
+                .and_then(|millis| origin.checked_add_signed(TimeDelta::milliseconds(millis)))
+                .ok_or_else(|| format!("bucket {n} of {duration}ms is out of range"))
+        };
+        collect_buckets(interval, elapsed.div_euclid(duration), nth)
+    }
+}
+
+// nth(n) is the start of bucket n, estimate a guess at the bucket the interval starts in
+fn collect_buckets(
+    interval: &Interval,
+    estimate: i64,
+    nth: impl Fn(i64) -> Result<DateTime<FixedOffset>, String>,
+) -> Result<Vec<Interval>, String> {
+    let mut n = estimate;
+    while nth(n)? > interval.start() {
+        n -= 1;
+    }
+    while nth(n + 1)? <= interval.start() {
+        n += 1;
+    }
+
+    let mut buckets = vec![];
+    let mut start = nth(n)?;
+    while start < interval.end() {
+        if buckets.len() == MAX_BUCKETS {
+            return Err(format!("{interval} has more than {MAX_BUCKETS} buckets"));
+        }
+        n += 1;
+        let end = nth(n)?;
+        buckets.push(Interval::new(start, end)?);
+        start = end;
+    }
+    Ok(buckets)
+}
+
+fn serialize_origin<S: Serializer>(
+    origin: &Option<DateTime<FixedOffset>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match origin {
+        Some(origin) => serializer.serialize_str(&format_time(origin)),
+        None => serializer.serialize_none(),
+    }
+}
+
+fn deserialize_origin<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<DateTime<FixedOffset>>, D::Error> {
+    Option::<String>::deserialize(deserializer)?
+        .map(|origin| parse_time(&origin))
+        .transpose()
+        .map_err(serde::de::Error::custom)
+}
+
+impl QueryComponent for Granularity {
+    fn validate_type(&self) -> bool {
+        match self {
+            Granularity::Period(granularity) => {
+                !granularity.period.is_zero()
+                    && granularity
+                        .time_zone
+                        .as_ref()
+                        .is_none_or(|time_zone| !time_zone.is_empty())
+            }
+            Granularity::Duration(granularity) => granularity.duration > 0,
+            _ => true,
+        }
+    }
+}
+
+impl QueryComponent for Option<Granularity> {
+    fn validate_type(&self) -> bool {
+        self.as_ref()
+            .is_none_or(|granularity| granularity.validate_type())
+    }
+    fn adapt(&mut self, version: &DruidVersion) -> Result<(), UnsupportedFeature> {
+        self.as_mut()
+            .map_or(Ok(()), |granularity| granularity.adapt(version))
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -24,7 +270,7 @@ pub struct GranularitySpec {
     segment_granularity: Granularity,
     query_granularity: Granularity,
     rollup: Option<bool>,
-    intervals: Option<Vec<String>>,
+    intervals: Option<Vec<Interval>>,
 }
 
 impl QueryComponent for GranularitySpec {
@@ -33,3 +279,147 @@ impl QueryComponent for GranularitySpec {
         true
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::query::ExtractionFunction;
+    use serde_json::json;
+
+    #[test]
+    fn test_granularity_forms() {
+        let simple: Granularity = serde_json::from_value(json!("fifteen_minute")).unwrap();
+        assert_eq!(simple, Granularity::FifteenMinute);
+        assert_eq!(
+            serde_json::to_value(&simple).unwrap(),
+            json!("fifteen_minute")
+        );
+
+        let weekly = json!({
+            "type": "period",
+            "period": "P1W",
+            "timeZone": "America/New_York",
+            "origin": "2024-01-01T00:00:00.000-05:00"
+        });
+        let granularity: Granularity = serde_json::from_value(weekly.clone()).unwrap();
+        let Granularity::Period(period) = &granularity else {
+            panic!("expected a period granularity, got {granularity:?}");
+        };
+        assert_eq!(period.period.weeks, 1);
+        assert_eq!(period.origin.unwrap().offset().local_minus_utc(), -5 * 3600);
+        assert_eq!(serde_json::to_value(&granularity).unwrap(), weekly);
+
+        let hourly =
+            json!({"type": "duration", "duration": 3600000, "origin": "1970-01-01T00:30:00.000Z"});
+        let granularity: Granularity = serde_json::from_value(hourly.clone()).unwrap();
+        assert!(matches!(granularity, Granularity::Duration(ref d) if d.duration == 3600000));
+        assert_eq!(serde_json::to_value(&granularity).unwrap(), hourly);
+        assert_eq!(
+            serde_json::to_value(Granularity::duration(60000)).unwrap(),
+            json!({"type": "duration", "duration": 60000, "origin": null})
+        );
+
+        assert!(!Granularity::period(Period::default()).validate_type());
+        assert!(!Granularity::duration(0).validate_type());
+        assert!(serde_json::from_value::<Granularity>(json!("fortnight")).is_err());
+
+        let time_format = json!({
+            "type": "timeFormat",
+            "format": "yyyy-MM-dd",
+            "timeZone": "America/New_York",
+            "locale": null,
+            "granularity": {"type": "period", "period": "P1M", "timeZone": "America/New_York"},
+            "asMillis": null
+        });
+        let extraction: ExtractionFunction = serde_json::from_value(time_format).unwrap();
+        assert!(matches!(
+            extraction,
+            ExtractionFunction::TimeFormat {
+                granularity: Some(Granularity::Period(_)),
+                ..
+            }
+        ));
+    }
+
+    fn starts(granularity: &Granularity, interval: &str) -> Vec<String> {
+        let buckets = granularity.buckets(&interval.parse().unwrap()).unwrap();
+        buckets
+            .iter()
+            .map(|bucket| format_time(&bucket.start()))
+            .collect()
+    }
+
+    #[test]
+    fn test_buckets() {
+        assert_eq!(
+            starts(&Granularity::Day, "2024-03-09T12:00Z/2024-03-11"),
+            vec!["2024-03-09T00:00:00.000Z", "2024-03-10T00:00:00.000Z"]
+        );
+        assert_eq!(
+            starts(&Granularity::Week, "2024-03-06/2024-03-12"),
+            vec!["2024-03-04T00:00:00.000Z", "2024-03-11T00:00:00.000Z"]
+        );
+        assert_eq!(
+            starts(&Granularity::Quarter, "2024-02-01/2024-05-01"),
+            vec!["2024-01-01T00:00:00.000Z", "2024-04-01T00:00:00.000Z"]
+        );
+
+        // The day clocks spring forward in New York is an hour shorter
+        let new_york = Granularity::Period(PeriodGranularity {
+            period: Period::days(1),
+            time_zone: Some("America/New_York".to_string()),
+            origin: None,
+        });
+        let buckets = new_york
+            .buckets(&"2024-03-09T05:00Z/2024-03-11T04:00Z".parse().unwrap())
+            .unwrap();
+        assert_eq!(
+            format_time(&buckets[1].start()),
+            "2024-03-10T00:00:00.000-05:00"
+        );
+        assert_eq!(buckets[1].duration(), TimeDelta::hours(23));
+        assert_eq!(
+            format_time(&buckets[1].end()),
+            "2024-03-11T00:00:00.000-04:00"
+        );
+
+        // Fortnights counted from a Wednesday origin
+        let fortnight = Granularity::Period(PeriodGranularity {
+            period: "P2W".parse().unwrap(),
+            time_zone: None,
+            origin: Some(parse_time("2024-01-03").unwrap()),
+        });
+        assert_eq!(
+            starts(&fortnight, "2023-12-25/2024-01-20"),
+            vec![
+                "2023-12-20T00:00:00.000Z",
+                "2024-01-03T00:00:00.000Z",
+                "2024-01-17T00:00:00.000Z"
+            ]
+        );
+
+        let half_hours = Granularity::Duration(DurationGranularity {
+            duration: 1_800_000,
+            origin: Some(parse_time("2024-01-01T00:15Z").unwrap()),
+        });
+        assert_eq!(
+            starts(&half_hours, "2024-01-01T01:00Z/2024-01-01T02:00Z"),
+            vec![
+                "2024-01-01T00:45:00.000Z",
+                "2024-01-01T01:15:00.000Z",
+                "2024-01-01T01:45:00.000Z"
+            ]
+        );
+
+        let day: Interval = "2024-01-01/2024-01-02".parse().unwrap();
+        assert_eq!(Granularity::All.buckets(&day).unwrap(), vec![day]);
+        assert!(Granularity::None.buckets(&day).is_err());
+        assert!(Granularity::Second.buckets(&Interval::eternity()).is_err());
+        let nowhere = Granularity::Period(PeriodGranularity {
+            period: Period::days(1),
+            time_zone: Some("Mars/Olympus_Mons".to_string()),
+            origin: None,
+        });
+        assert!(nowhere.buckets(&day).is_err());
+    }
+}
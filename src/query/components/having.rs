@@ -1,5 +1,5 @@
 use crate::query::components::model::QueryComponent;
-use crate::query::{DimensionSpec, Filter, FloatingPointNumber};
+use crate::query::{DimensionSpec, DruidVersion, Filter, FloatingPointNumber, UnsupportedFeature};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -52,10 +52,25 @@ impl QueryComponent for Having {
             Having::Not { .. } => true,
         }
     }
+
+    fn adapt(&mut self, version: &DruidVersion) -> Result<(), UnsupportedFeature> {
+        match self {
+            Having::Filter { filter } => filter.adapt(version),
+            Having::And { having_specs }
+            | Having::Or { having_specs }
+            | Having::Not { having_specs } => having_specs
+                .iter_mut()
+                .try_for_each(|having| having.adapt(version)),
+            _ => Ok(()),
+        }
+    }
 }
 
 impl QueryComponent for Option<Having> {
     fn validate_type(&self) -> bool {
         self.clone().is_none_or(|having| having.validate_type())
     }
+    fn adapt(&mut self, version: &DruidVersion) -> Result<(), UnsupportedFeature> {
+        self.as_mut().map_or(Ok(()), |having| having.adapt(version))
+    }
 }
@@ -1,8 +1,5 @@
 use serde::{Deserialize, Serialize};
 
-pub type Interval = String; // TODO
-pub type Expression = String;
-
 #[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub enum ResultFormat {
@@ -0,0 +1,400 @@
+use crate::query::Period;
+use chrono::{
+    DateTime, FixedOffset, NaiveDate, NaiveDateTime, SecondsFormat, TimeDelta, TimeZone, Utc,
+};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt::{Display, Formatter};
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+
+// What Druid writes for Intervals.ETERNITY, the bounds are outside what chrono can represent
+const ETERNITY: &str = "-146136543-09-08T08:23:32.096Z/146140482-04-24T15:36:27.903Z";
+
+// Remembers how it was written so it goes back to Druid the same way
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum IntervalForm {
+    Bounds,
+    StartPeriod(Period),
+    PeriodEnd(Period),
+    Eternity,
+}
+
+// ISO-8601 interval, start inclusive and end exclusive. Timestamps without an offset are UTC,
+// which is what Druid assumes as well.
+#[derive(Debug, Clone, Copy)]
+pub struct Interval {
+    start: DateTime<FixedOffset>,
+    end: DateTime<FixedOffset>,
+    form: IntervalForm,
+}
+
+// The form only matters for serialization, 2020-01-01/P1D is the same interval as its bounds
+impl PartialEq for Interval {
+    fn eq(&self, other: &Self) -> bool {
+        self.start == other.start && self.end == other.end
+    }
+}
+
+impl Eq for Interval {}
+
+impl Hash for Interval {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.start.hash(state);
+        self.end.hash(state);
+    }
+}
+
+impl Interval {
+    pub fn new(
+        start: impl Into<DateTime<FixedOffset>>,
+        end: impl Into<DateTime<FixedOffset>>,
+    ) -> Result<Self, String> {
+        Self::checked(start.into(), end.into(), IntervalForm::Bounds)
+    }
+
+    // Milliseconds since the epoch, like __time
+    pub fn from_millis(start: i64, end: i64) -> Result<Self, String> {
+        let timestamp = |millis| {
+            DateTime::from_timestamp_millis(millis)
+                .ok_or_else(|| format!("timestamp {millis} is out of range"))
+        };
+        Self::new(timestamp(start)?, timestamp(end)?)
+    }
+
+    pub fn from_duration(
+        start: impl Into<DateTime<FixedOffset>>,
+        duration: TimeDelta,
+    ) -> Result<Self, String> {
+        let start = start.into();
+        let end = start
+            .checked_add_signed(duration)
+            .ok_or_else(|| format!("{start} plus {duration} is out of range"))?;
+        Self::new(start, end)
+    }
+
+    // Written as start/period
+    pub fn after(start: impl Into<DateTime<FixedOffset>>, period: Period) -> Result<Self, String> {
+        let start = start.into();
+        let end = period
+            .add_to(start)
+            .ok_or_else(|| format!("{start} plus {period} is out of range"))?;
+        Self::checked(start, end, IntervalForm::StartPeriod(period))
+    }
+
+    // Written as period/end
+    pub fn before(period: Period, end: impl Into<DateTime<FixedOffset>>) -> Result<Self, String> {
+        let end = end.into();
+        let start = period
+            .subtract_from(end)
+            .ok_or_else(|| format!("{end} minus {period} is out of range"))?;
+        Self::checked(start, end, IntervalForm::PeriodEnd(period))
+    }
+
+    pub fn eternity() -> Self {
+        Self {
+            start: DateTime::<Utc>::MIN_UTC.fixed_offset(),
+            end: DateTime::<Utc>::MAX_UTC.fixed_offset(),
+            form: IntervalForm::Eternity,
+        }
+    }
+
+    fn checked(
+        start: DateTime<FixedOffset>,
+        end: DateTime<FixedOffset>,
+        form: IntervalForm,
+    ) -> Result<Self, String> {
+        if end <= start {
+            return Err(format!(
+                "interval end {} does not come after its start {}",
+                format_time(&end),
+                format_time(&start)
+            ));
+        }
+        Ok(Self { start, end, form })
+    }
+
+    pub fn start(&self) -> DateTime<FixedOffset> {
+        self.start
+    }
+
+    pub fn end(&self) -> DateTime<FixedOffset> {
+        self.end
+    }
+
+    pub fn duration(&self) -> TimeDelta {
+        self.end - self.start
+    }
+
+    pub fn is_eternity(&self) -> bool {
+        self.form == IntervalForm::Eternity
+    }
+
+    pub fn contains_time<Tz: TimeZone>(&self, time: &DateTime<Tz>) -> bool {
+        self.start <= *time && *time < self.end
+    }
+
+    pub fn contains(&self, other: &Interval) -> bool {
+        self.start <= other.start && other.end <= self.end
+    }
+
+    // Intervals that only touch don't overlap
+    pub fn overlaps(&self, other: &Interval) -> bool {
+        self.start < other.end && other.start < self.end
+    }
+
+    pub fn intersection(&self, other: &Interval) -> Option<Interval> {
+        let start = self.start.max(other.start);
+        let end = self.end.min(other.end);
+        Self::checked(start, end, IntervalForm::Bounds).ok()
+    }
+
+    pub fn split_at<Tz: TimeZone>(&self, time: &DateTime<Tz>) -> Option<(Interval, Interval)> {
+        let time = time.fixed_offset();
+        Some((
+            Self::checked(self.start, time, IntervalForm::Bounds).ok()?,
+            Self::checked(time, self.end, IntervalForm::Bounds).ok()?,
+        ))
+    }
+
+    // Consecutive pieces of one period each counted from the start, the last one may be shorter
+    pub fn split(&self, period: &Period) -> Vec<Interval> {
+        let mut pieces = vec![];
+        let mut start = self.start;
+        while let Some(end) = period
+            .add_to(start)
+            .filter(|end| *end > start && *end < self.end)
+        {
+            pieces.push(Self {
+                start,
+                end,
+                form: IntervalForm::Bounds,
+            });
+            start = end;
+        }
+        pieces.push(Self {
+            start,
+            end: self.end,
+            form: IntervalForm::Bounds,
+        });
+        pieces
+    }
+}
+
+pub(crate) fn format_time(time: &DateTime<FixedOffset>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+// Accepts what Joda does in practice: 2015-09-12, 2015-09-12T08, 2015-09-12T08:30,
+// 2015-09-12T08:30:00.000, each optionally followed by Z, +05:30, +0530 or +05
+pub(crate) fn parse_time(s: &str) -> Result<DateTime<FixedOffset>, String> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(s) {
+        return Ok(time);
+    }
+    let invalid = || format!("invalid ISO-8601 timestamp {s:?}");
+    let (local, offset) = split_offset(s).ok_or_else(invalid)?;
+    let naive = match local.split_once('T') {
+        Some((date, time)) => {
+            let date = NaiveDate::parse_from_str(date, "%Y-%m-%d").map_err(|_| invalid())?;
+            let time = match time.len() {
+                2 => format!("{time}:00"),
+                _ => time.to_string(),
+            };
+            ["%H:%M:%S%.f", "%H:%M"]
+                .iter()
+                .find_map(|format| chrono::NaiveTime::parse_from_str(&time, format).ok())
+                .map(|time| NaiveDateTime::new(date, time))
+                .ok_or_else(invalid)?
+        }
+        None => {
+            let date = match local.len() {
+                4 => format!("{local}-01-01"),
+                7 => format!("{local}-01"),
+                _ => local.to_string(),
+            };
+            NaiveDate::parse_from_str(&date, "%Y-%m-%d")
+                .map_err(|_| invalid())?
+                .and_time(chrono::NaiveTime::MIN)
+        }
+    };
+    offset
+        .from_local_datetime(&naive)
+        .single()
+        .ok_or_else(invalid)
+}
+
+fn split_offset(s: &str) -> Option<(&str, FixedOffset)> {
+    let utc = FixedOffset::east_opt(0)?;
+    if let Some(local) = s.strip_suffix('Z') {
+        return Some((local, utc));
+    }
+    // A date's dashes aren't an offset, only look after the time designator
+    let Some(index) = s
+        .find('T')
+        .and_then(|t| s[t..].rfind(['+', '-']).map(|i| t + i))
+    else {
+        return Some((s, utc));
+    };
+    let (local, offset) = s.split_at(index);
+    let sign = if offset.starts_with('-') { -1 } else { 1 };
+    let digits = offset[1..].replace(':', "");
+    let (hours, minutes) = match digits.len() {
+        2 => (digits.parse::<i32>().ok()?, 0),
+        4 => (
+            digits[..2].parse::<i32>().ok()?,
+            digits[2..].parse::<i32>().ok()?,
+        ),
+        _ => return None,
+    };
+    Some((
+        local,
+        FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))?,
+    ))
+}
+
+impl Display for Interval {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.form {
+            IntervalForm::Bounds => {
+                write!(f, "{}/{}", format_time(&self.start), format_time(&self.end))
+            }
+            IntervalForm::StartPeriod(period) => {
+                write!(f, "{}/{period}", format_time(&self.start))
+            }
+            IntervalForm::PeriodEnd(period) => write!(f, "{period}/{}", format_time(&self.end)),
+            IntervalForm::Eternity => f.write_str(ETERNITY),
+        }
+    }
+}
+
+impl FromStr for Interval {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s == "ETERNITY" || s == ETERNITY {
+            return Ok(Self::eternity());
+        }
+        let (start, end) = s
+            .split_once('/')
+            .ok_or_else(|| format!("invalid ISO-8601 interval {s:?}"))?;
+        match (start.starts_with('P'), end.starts_with('P')) {
+            (false, false) => Self::new(parse_time(start)?, parse_time(end)?),
+            (false, true) => Self::after(parse_time(start)?, end.parse()?),
+            (true, false) => Self::before(start.parse()?, parse_time(end)?),
+            (true, true) => Err(format!("interval {s:?} has no timestamp")),
+        }
+    }
+}
+
+impl Serialize for Interval {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Interval {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(year: i32, month: u32, day: u32, hour: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(year, month, day, hour, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn test_interval_forms() {
+        let day: Interval = "2015-09-12/2015-09-13".parse().unwrap();
+        assert_eq!(day.start(), utc(2015, 9, 12, 0));
+        assert_eq!(day.duration(), TimeDelta::days(1));
+        assert_eq!(
+            day.to_string(),
+            "2015-09-12T00:00:00.000Z/2015-09-13T00:00:00.000Z"
+        );
+
+        let after: Interval = "2015-09-12T08:00:00+05:30/PT12H".parse().unwrap();
+        assert_eq!(after.start(), utc(2015, 9, 12, 2) + TimeDelta::minutes(30));
+        assert_eq!(after.to_string(), "2015-09-12T08:00:00.000+05:30/PT12H");
+
+        let before: Interval = "P1M/2015-03-01T00-0800".parse().unwrap();
+        assert_eq!(before.start(), utc(2015, 2, 1, 8));
+        assert_eq!(before.to_string(), "P1M/2015-03-01T00:00:00.000-08:00");
+
+        let eternity: Interval = "ETERNITY".parse().unwrap();
+        assert!(eternity.is_eternity());
+        assert!(eternity.contains(&day));
+        assert_eq!(
+            serde_json::to_string(&eternity).unwrap(),
+            format!("\"{ETERNITY}\"")
+        );
+        assert_eq!(
+            serde_json::from_str::<Interval>(&format!("\"{ETERNITY}\"")).unwrap(),
+            eternity
+        );
+
+        let roundtrip: Interval =
+            serde_json::from_value(serde_json::to_value(after).unwrap()).unwrap();
+        assert_eq!(roundtrip, after);
+
+        for invalid in [
+            "2015-09-13/2015-09-12",
+            "2015-09-12/2015-09-12",
+            "P1D/P2D",
+            "2015-09-12",
+            "2015-09-12/tomorrow",
+            "2015-02-30/2015-03-01",
+        ] {
+            assert!(invalid.parse::<Interval>().is_err(), "{invalid}");
+        }
+        assert!(Interval::new(utc(2015, 9, 13, 0), utc(2015, 9, 12, 0)).is_err());
+    }
+
+    #[test]
+    fn test_interval_helpers() {
+        let day = Interval::from_duration(utc(2015, 9, 12, 0), TimeDelta::days(1)).unwrap();
+        let evening = Interval::new(utc(2015, 9, 12, 18), utc(2015, 9, 13, 6)).unwrap();
+        let next_day = Interval::after(utc(2015, 9, 13, 0), Period::days(1)).unwrap();
+
+        assert!(day.overlaps(&evening));
+        assert!(!day.overlaps(&next_day));
+        assert!(!day.contains(&evening));
+        assert!(day.contains_time(&utc(2015, 9, 12, 23)));
+        assert!(!day.contains_time(&utc(2015, 9, 13, 0)));
+        assert_eq!(
+            day.intersection(&evening).unwrap(),
+            Interval::new(utc(2015, 9, 12, 18), utc(2015, 9, 13, 0)).unwrap()
+        );
+        assert_eq!(day.intersection(&next_day), None);
+
+        let (morning, rest) = day.split_at(&utc(2015, 9, 12, 12)).unwrap();
+        assert_eq!(morning.end(), rest.start());
+        assert!(day.split_at(&utc(2015, 9, 12, 0)).is_none());
+
+        let pieces = day.split(&Period::hours(5));
+        assert_eq!(pieces.len(), 5);
+        assert_eq!(pieces[4].duration(), TimeDelta::hours(4));
+        assert_eq!(pieces[4].end(), day.end());
+
+        assert_eq!(
+            Interval::from_millis(1442016000000, 1442102400000).unwrap(),
+            day
+        );
+
+        let period: Interval = "2020-01-01/P1D".parse().unwrap();
+        let bounds: Interval = "2020-01-01T00Z/2020-01-02T00Z".parse().unwrap();
+        assert_eq!(period, bounds);
+        assert_ne!(period.to_string(), bounds.to_string());
+        let hash = |interval: &Interval| {
+            let mut hasher = std::collections::hash_map::DefaultHasher::new();
+            interval.hash(&mut hasher);
+            hasher.finish()
+        };
+        assert_eq!(hash(&period), hash(&bounds));
+    }
+}
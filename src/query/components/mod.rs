@@ -2,14 +2,19 @@ mod aggregation;
 mod context;
 mod datasource;
 mod dimension;
+mod expression;
 mod extraction;
 mod filter;
 mod granularity;
 mod having;
 mod helpers;
+mod interval;
 mod limit;
 mod model;
+mod period;
 mod searchquery;
+mod sketch;
+mod statistics;
 mod toinclude;
 mod topnmetric;
 mod virtualcolumn;
@@ -18,14 +23,19 @@ pub use aggregation::*;
 pub use context::*;
 pub use datasource::*;
 pub use dimension::*;
+pub use expression::*;
 pub use extraction::*;
 pub use filter::*;
 pub use granularity::*;
 pub use having::*;
 pub use helpers::*;
+pub use interval::*;
 pub use limit::*;
 pub use model::*;
+pub use period::*;
 pub use searchquery::*;
+pub use sketch::*;
+pub use statistics::*;
 pub use toinclude::*;
 pub use topnmetric::*;
 pub use virtualcolumn::*;
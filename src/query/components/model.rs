@@ -1,3 +1,27 @@
+use crate::query::{DruidVersion, UnsupportedFeature};
+
 pub trait QueryComponent {
     fn validate_type(&self) -> bool;
+
+    // Rewrites what has an equivalent in the target version, fails on what it can't run
+    fn adapt(&mut self, _version: &DruidVersion) -> Result<(), UnsupportedFeature> {
+        Ok(())
+    }
+
+    // Columns read from the data source or from virtual columns of the same query
+    fn columns(&self) -> Vec<&str> {
+        vec![]
+    }
+}
+
+impl<T: QueryComponent> QueryComponent for Box<T> {
+    fn validate_type(&self) -> bool {
+        self.as_ref().validate_type()
+    }
+    fn adapt(&mut self, version: &DruidVersion) -> Result<(), UnsupportedFeature> {
+        self.as_mut().adapt(version)
+    }
+    fn columns(&self) -> Vec<&str> {
+        self.as_ref().columns()
+    }
 }
@@ -0,0 +1,237 @@
+use chrono::{DateTime, Days, Months, TimeDelta, TimeZone};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+// ISO-8601 period such as P1D, PT15M or P1Y2M10DT2H30M. Years, months and days are calendar
+// fields, so their length depends on the time and time zone they're added to.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Period {
+    pub years: u32,
+    pub months: u32,
+    pub weeks: u32,
+    pub days: u32,
+    pub hours: u32,
+    pub minutes: u32,
+    pub seconds: u32,
+    pub millis: u32,
+}
+
+impl Period {
+    pub fn days(days: u32) -> Self {
+        Self {
+            days,
+            ..Default::default()
+        }
+    }
+
+    pub fn hours(hours: u32) -> Self {
+        Self {
+            hours,
+            ..Default::default()
+        }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        *self == Self::default()
+    }
+
+    pub(crate) fn times(&self, n: u32) -> Option<Period> {
+        Some(Period {
+            years: self.years.checked_mul(n)?,
+            months: self.months.checked_mul(n)?,
+            weeks: self.weeks.checked_mul(n)?,
+            days: self.days.checked_mul(n)?,
+            hours: self.hours.checked_mul(n)?,
+            minutes: self.minutes.checked_mul(n)?,
+            seconds: self.seconds.checked_mul(n)?,
+            millis: self.millis.checked_mul(n)?,
+        })
+    }
+
+    // Average length, only good for estimates since calendar fields vary
+    pub(crate) fn nominal_millis(&self) -> f64 {
+        const DAY: f64 = 86_400_000.0;
+        f64::from(self.years) * 365.2425 * DAY
+            + f64::from(self.months) * 30.436875 * DAY
+            + f64::from(self.weeks) * 7.0 * DAY
+            + f64::from(self.days) * DAY
+            + self.time().num_milliseconds() as f64
+    }
+
+    fn calendar_months(&self) -> Option<Months> {
+        Some(Months::new(
+            self.years.checked_mul(12)?.checked_add(self.months)?,
+        ))
+    }
+
+    fn calendar_days(&self) -> Days {
+        Days::new(u64::from(self.weeks) * 7 + u64::from(self.days))
+    }
+
+    fn time(&self) -> TimeDelta {
+        TimeDelta::hours(self.hours.into())
+            + TimeDelta::minutes(self.minutes.into())
+            + TimeDelta::seconds(self.seconds.into())
+            + TimeDelta::milliseconds(self.millis.into())
+    }
+
+    // Largest fields first, like Joda, so P1M1D from 2023-01-31 is 2023-03-01
+    pub fn add_to<Tz: TimeZone>(&self, time: DateTime<Tz>) -> Option<DateTime<Tz>> {
+        time.checked_add_months(self.calendar_months()?)?
+            .checked_add_days(self.calendar_days())?
+            .checked_add_signed(self.time())
+    }
+
+    pub fn subtract_from<Tz: TimeZone>(&self, time: DateTime<Tz>) -> Option<DateTime<Tz>> {
+        time.checked_sub_months(self.calendar_months()?)?
+            .checked_sub_days(self.calendar_days())?
+            .checked_sub_signed(self.time())
+    }
+}
+
+impl Display for Period {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.is_zero() {
+            return f.write_str("PT0S");
+        }
+        f.write_str("P")?;
+        for (value, designator) in [
+            (self.years, 'Y'),
+            (self.months, 'M'),
+            (self.weeks, 'W'),
+            (self.days, 'D'),
+        ] {
+            if value > 0 {
+                write!(f, "{value}{designator}")?;
+            }
+        }
+        if self.hours + self.minutes + self.seconds + self.millis > 0 {
+            f.write_str("T")?;
+        }
+        for (value, designator) in [(self.hours, 'H'), (self.minutes, 'M')] {
+            if value > 0 {
+                write!(f, "{value}{designator}")?;
+            }
+        }
+        match (self.seconds, self.millis) {
+            (0, 0) => Ok(()),
+            (seconds, 0) => write!(f, "{seconds}S"),
+            (seconds, millis) => write!(f, "{seconds}.{millis:03}S"),
+        }
+    }
+}
+
+impl FromStr for Period {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid ISO-8601 period {s:?}");
+        let number = |digits: &str| digits.parse::<u32>().map_err(|_| invalid());
+        let rest = s.trim().strip_prefix('P').ok_or_else(invalid)?;
+        let (date, time) = match rest.split_once('T') {
+            Some((_, "")) => return Err(format!("{}: empty time part", invalid())),
+            Some((date, time)) => (date, time),
+            None => (rest, ""),
+        };
+
+        let mut period = Period::default();
+        let mut fields = 0;
+        for (part, is_time) in [(date, false), (time, true)] {
+            let mut digits = String::new();
+            for c in part.chars() {
+                if c.is_ascii_digit() || c == '.' || c == ',' {
+                    digits.push(c);
+                    continue;
+                }
+                if digits.is_empty() {
+                    return Err(invalid());
+                }
+                match (is_time, c) {
+                    (false, 'Y') => period.years = number(&digits)?,
+                    (false, 'M') => period.months = number(&digits)?,
+                    (false, 'W') => period.weeks = number(&digits)?,
+                    (false, 'D') => period.days = number(&digits)?,
+                    (true, 'H') => period.hours = number(&digits)?,
+                    (true, 'M') => period.minutes = number(&digits)?,
+                    (true, 'S') => {
+                        let (seconds, fraction) =
+                            digits.split_once(['.', ',']).unwrap_or((&digits, ""));
+                        period.seconds = number(seconds)?;
+                        if !fraction.is_empty() {
+                            // Anything below a millisecond is dropped
+                            let millis: String =
+                                fraction.chars().chain("000".chars()).take(3).collect();
+                            period.millis = number(&millis)?;
+                        }
+                    }
+                    _ => return Err(invalid()),
+                }
+                digits.clear();
+                fields += 1;
+            }
+            if !digits.is_empty() {
+                return Err(invalid());
+            }
+        }
+        if fields == 0 {
+            return Err(invalid());
+        }
+        Ok(period)
+    }
+}
+
+impl Serialize for Period {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Period {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    #[test]
+    fn test_period_parsing() {
+        let period: Period = "P1Y2M10DT2H30M1.5S".parse().unwrap();
+        assert_eq!(
+            period,
+            Period {
+                years: 1,
+                months: 2,
+                days: 10,
+                hours: 2,
+                minutes: 30,
+                seconds: 1,
+                millis: 500,
+                ..Default::default()
+            }
+        );
+        assert_eq!(period.to_string(), "P1Y2M10DT2H30M1.500S");
+        assert_eq!("P1W".parse::<Period>().unwrap().to_string(), "P1W");
+        assert_eq!("PT0S".parse::<Period>().unwrap(), Period::default());
+        for invalid in ["", "P", "PT", "1D", "P1H", "PT1D", "P1.5"] {
+            assert!(invalid.parse::<Period>().is_err(), "{invalid}");
+        }
+
+        let jan_31 = Utc.with_ymd_and_hms(2024, 1, 31, 0, 0, 0).unwrap();
+        let month: Period = "P1M".parse().unwrap();
+        assert_eq!(
+            month.add_to(jan_31).unwrap(),
+            Utc.with_ymd_and_hms(2024, 2, 29, 0, 0, 0).unwrap()
+        );
+        assert_eq!(
+            Period::hours(12).subtract_from(jan_31).unwrap(),
+            Utc.with_ymd_and_hms(2024, 1, 30, 12, 0, 0).unwrap()
+        );
+    }
+}
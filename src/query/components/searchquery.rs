@@ -3,7 +3,7 @@ use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
-pub enum SearchQuery {
+pub enum SearchQuerySpec {
     InsensitiveContains {
         value: String,
     },
@@ -20,14 +20,14 @@ pub enum SearchQuery {
     },
 }
 
-impl QueryComponent for SearchQuery {
+impl QueryComponent for SearchQuerySpec {
     fn validate_type(&self) -> bool {
         match self {
             //TODO
-            SearchQuery::InsensitiveContains { .. } => true,
-            SearchQuery::Fragment { .. } => true,
-            SearchQuery::Contains { .. } => true,
-            SearchQuery::Regex { .. } => true,
+            SearchQuerySpec::InsensitiveContains { .. } => true,
+            SearchQuerySpec::Fragment { .. } => true,
+            SearchQuerySpec::Contains { .. } => true,
+            SearchQuerySpec::Regex { .. } => true,
         }
     }
 }
@@ -0,0 +1,384 @@
+use crate::query::components::model::QueryComponent;
+use crate::query::{
+    DruidVersion, FloatingPointNumber, IntegerNumber, PostAggregation, UnsupportedFeature,
+};
+use serde::{Deserialize, Serialize};
+
+// Aggregators of the druid-datasketches extension, which has to be loaded on the cluster
+// https://druid.apache.org/docs/latest/development/extensions-core/datasketches-extension
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "type")]
+pub enum SketchAggregation {
+    #[serde(rename = "thetaSketch", rename_all = "camelCase")]
+    Theta {
+        name: String,
+        field_name: String,
+        is_input_theta_sketch: Option<bool>,
+        size: Option<IntegerNumber>,
+        should_finalize: Option<bool>,
+    },
+
+    #[serde(rename = "HLLSketchBuild", rename_all = "camelCase")]
+    HllBuild {
+        name: String,
+        field_name: String,
+        lg_k: Option<IntegerNumber>,
+        tgt_hll_type: Option<HllType>,
+        string_encoding: Option<StringEncoding>,
+        should_finalize: Option<bool>,
+        round: Option<bool>,
+    },
+
+    #[serde(rename = "HLLSketchMerge", rename_all = "camelCase")]
+    HllMerge {
+        name: String,
+        field_name: String,
+        lg_k: Option<IntegerNumber>,
+        tgt_hll_type: Option<HllType>,
+        should_finalize: Option<bool>,
+        round: Option<bool>,
+    },
+
+    #[serde(rename = "quantilesDoublesSketch", rename_all = "camelCase")]
+    QuantilesDoubles {
+        name: String,
+        field_name: String,
+        k: Option<IntegerNumber>,
+        max_stream_length: Option<IntegerNumber>,
+        should_finalize: Option<bool>,
+    },
+
+    #[serde(rename = "KllDoublesSketch", rename_all = "camelCase")]
+    KllDoubles {
+        name: String,
+        field_name: String,
+        k: Option<IntegerNumber>,
+        max_stream_length: Option<IntegerNumber>,
+        should_finalize: Option<bool>,
+    },
+
+    // Keyed by field_name, with one double per metric column
+    #[serde(rename = "arrayOfDoublesSketch", rename_all = "camelCase")]
+    ArrayOfDoubles {
+        name: String,
+        field_name: String,
+        nominal_entries: Option<IntegerNumber>,
+        metric_columns: Option<Vec<String>>,
+        number_of_values: Option<IntegerNumber>,
+    },
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq)]
+pub enum HllType {
+    #[serde(rename = "HLL_4")]
+    Hll4,
+    #[serde(rename = "HLL_6")]
+    Hll6,
+    #[serde(rename = "HLL_8")]
+    Hll8,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum StringEncoding {
+    Utf16le,
+    Utf8,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum SetOperation {
+    Union,
+    Intersect,
+    Not,
+}
+
+// Post aggregators read the sketch through a field, usually a fieldAccess of the aggregator
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "type")]
+pub enum SketchPostAggregation {
+    #[serde(rename = "thetaSketchEstimate", rename_all = "camelCase")]
+    ThetaEstimate {
+        name: String,
+        field: Box<PostAggregation>,
+        error_bounds_std_dev: Option<IntegerNumber>,
+    },
+
+    #[serde(rename = "thetaSketchSetOp", rename_all = "camelCase")]
+    ThetaSetOp {
+        name: String,
+        func: SetOperation,
+        fields: Vec<PostAggregation>,
+        size: Option<IntegerNumber>,
+    },
+
+    #[serde(rename = "HLLSketchEstimateWithBounds", rename_all = "camelCase")]
+    HllEstimateWithBounds {
+        name: String,
+        field: Box<PostAggregation>,
+        num_std_dev: Option<IntegerNumber>,
+    },
+
+    #[serde(rename = "quantilesDoublesSketchToQuantiles")]
+    QuantilesDoublesToQuantiles {
+        name: String,
+        field: Box<PostAggregation>,
+        fractions: Vec<FloatingPointNumber>,
+    },
+
+    // Either split points or a number of equal bins between the minimum and maximum
+    #[serde(rename = "quantilesDoublesSketchToHistogram", rename_all = "camelCase")]
+    QuantilesDoublesToHistogram {
+        name: String,
+        field: Box<PostAggregation>,
+        split_points: Option<Vec<FloatingPointNumber>>,
+        num_bins: Option<IntegerNumber>,
+    },
+
+    #[serde(rename = "quantilesDoublesSketchToCDF", rename_all = "camelCase")]
+    QuantilesDoublesToCdf {
+        name: String,
+        field: Box<PostAggregation>,
+        split_points: Vec<FloatingPointNumber>,
+    },
+}
+
+// Theta and tuple sketches take a nominal entry count, quantiles sketches their k
+fn valid_size(size: &Option<IntegerNumber>, min: IntegerNumber, max: IntegerNumber) -> bool {
+    size.is_none_or(|size| size.is_power_of_two() && (min..=max).contains(&size))
+}
+
+fn valid_range(value: &Option<IntegerNumber>, min: IntegerNumber, max: IntegerNumber) -> bool {
+    value.is_none_or(|value| (min..=max).contains(&value))
+}
+
+fn increasing(points: &[FloatingPointNumber]) -> bool {
+    !points.is_empty()
+        && points.iter().all(|point| point.is_finite())
+        && points.windows(2).all(|pair| pair[0] < pair[1])
+}
+
+impl SketchAggregation {
+    pub fn name(&self) -> &str {
+        match self {
+            SketchAggregation::Theta { name, .. }
+            | SketchAggregation::HllBuild { name, .. }
+            | SketchAggregation::HllMerge { name, .. }
+            | SketchAggregation::QuantilesDoubles { name, .. }
+            | SketchAggregation::KllDoubles { name, .. }
+            | SketchAggregation::ArrayOfDoubles { name, .. } => name,
+        }
+    }
+}
+
+impl QueryComponent for SketchAggregation {
+    fn validate_type(&self) -> bool {
+        match self {
+            SketchAggregation::Theta { size, .. } => valid_size(size, 16, 1 << 26),
+            SketchAggregation::HllBuild { lg_k, .. } | SketchAggregation::HllMerge { lg_k, .. } => {
+                valid_range(lg_k, 4, 21)
+            }
+            SketchAggregation::QuantilesDoubles { k, .. } => valid_size(k, 2, 1 << 15),
+            SketchAggregation::KllDoubles { k, .. } => valid_range(k, 8, 65535),
+            SketchAggregation::ArrayOfDoubles {
+                nominal_entries,
+                metric_columns,
+                number_of_values,
+                ..
+            } => {
+                // Without numberOfValues there is one value per metric column
+                let columns = metric_columns
+                    .as_ref()
+                    .map(|columns| columns.len() as IntegerNumber);
+                valid_size(nominal_entries, 16, 1 << 26)
+                    && valid_range(&number_of_values.or(columns), 1, 127)
+                    && columns.is_none_or(|columns| number_of_values.is_none_or(|v| v == columns))
+            }
+        }
+    }
+
+    fn columns(&self) -> Vec<&str> {
+        match self {
+            SketchAggregation::ArrayOfDoubles {
+                field_name,
+                metric_columns,
+                ..
+            } => std::iter::once(field_name)
+                .chain(metric_columns.iter().flatten())
+                .map(String::as_str)
+                .collect(),
+            SketchAggregation::Theta { field_name, .. }
+            | SketchAggregation::HllBuild { field_name, .. }
+            | SketchAggregation::HllMerge { field_name, .. }
+            | SketchAggregation::QuantilesDoubles { field_name, .. }
+            | SketchAggregation::KllDoubles { field_name, .. } => vec![field_name],
+        }
+    }
+}
+
+impl SketchPostAggregation {
+    pub fn name(&self) -> &str {
+        match self {
+            SketchPostAggregation::ThetaEstimate { name, .. }
+            | SketchPostAggregation::ThetaSetOp { name, .. }
+            | SketchPostAggregation::HllEstimateWithBounds { name, .. }
+            | SketchPostAggregation::QuantilesDoublesToQuantiles { name, .. }
+            | SketchPostAggregation::QuantilesDoublesToHistogram { name, .. }
+            | SketchPostAggregation::QuantilesDoublesToCdf { name, .. } => name,
+        }
+    }
+
+    pub(crate) fn name_mut(&mut self) -> &mut String {
+        match self {
+            SketchPostAggregation::ThetaEstimate { name, .. }
+            | SketchPostAggregation::ThetaSetOp { name, .. }
+            | SketchPostAggregation::HllEstimateWithBounds { name, .. }
+            | SketchPostAggregation::QuantilesDoublesToQuantiles { name, .. }
+            | SketchPostAggregation::QuantilesDoublesToHistogram { name, .. }
+            | SketchPostAggregation::QuantilesDoublesToCdf { name, .. } => name,
+        }
+    }
+}
+
+impl QueryComponent for SketchPostAggregation {
+    fn validate_type(&self) -> bool {
+        match self {
+            SketchPostAggregation::ThetaEstimate {
+                field,
+                error_bounds_std_dev,
+                ..
+            } => field.validate_type() && valid_range(error_bounds_std_dev, 1, 3),
+            SketchPostAggregation::ThetaSetOp {
+                func, fields, size, ..
+            } => {
+                // NOT subtracts the other sketches from the first one
+                let min_fields = if *func == SetOperation::Not { 2 } else { 1 };
+                fields.len() >= min_fields
+                    && fields.iter().all(PostAggregation::validate_type)
+                    && valid_size(size, 16, 1 << 26)
+            }
+            SketchPostAggregation::HllEstimateWithBounds {
+                field, num_std_dev, ..
+            } => field.validate_type() && valid_range(num_std_dev, 1, 3),
+            SketchPostAggregation::QuantilesDoublesToQuantiles {
+                field, fractions, ..
+            } => {
+                field.validate_type()
+                    && !fractions.is_empty()
+                    && fractions
+                        .iter()
+                        .all(|fraction| (0.0..=1.0).contains(fraction))
+            }
+            SketchPostAggregation::QuantilesDoublesToHistogram {
+                field,
+                split_points,
+                num_bins,
+                ..
+            } => {
+                field.validate_type()
+                    && match (split_points, num_bins) {
+                        (Some(points), None) => increasing(points),
+                        (None, bins) => bins.is_none_or(|bins| bins >= 1),
+                        (Some(_), Some(_)) => false,
+                    }
+            }
+            SketchPostAggregation::QuantilesDoublesToCdf {
+                field,
+                split_points,
+                ..
+            } => field.validate_type() && increasing(split_points),
+        }
+    }
+
+    fn adapt(&mut self, version: &DruidVersion) -> Result<(), UnsupportedFeature> {
+        match self {
+            SketchPostAggregation::ThetaSetOp { fields, .. } => {
+                fields.iter_mut().try_for_each(|field| field.adapt(version))
+            }
+            SketchPostAggregation::ThetaEstimate { field, .. }
+            | SketchPostAggregation::HllEstimateWithBounds { field, .. }
+            | SketchPostAggregation::QuantilesDoublesToQuantiles { field, .. }
+            | SketchPostAggregation::QuantilesDoublesToHistogram { field, .. }
+            | SketchPostAggregation::QuantilesDoublesToCdf { field, .. } => field.adapt(version),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::query::Aggregation;
+    use serde_json::json;
+
+    fn field_access(field_name: &str) -> Box<PostAggregation> {
+        Box::new(PostAggregation::field(field_name))
+    }
+
+    #[test]
+    fn test_sketches() {
+        let aggregations: Vec<Aggregation> = serde_json::from_value(json!([
+            {"type": "thetaSketch", "name": "users", "fieldName": "user", "size": 32768},
+            {"type": "HLLSketchBuild", "name": "pages", "fieldName": "page", "lgK": 14, "tgtHllType": "HLL_8"},
+            {"type": "quantilesDoublesSketch", "name": "latency", "fieldName": "latency_ms", "k": 256},
+            {"type": "KllDoublesSketch", "name": "size", "fieldName": "bytes", "k": 200},
+            {"type": "arrayOfDoublesSketch", "name": "spend", "fieldName": "user", "metricColumns": ["price", "tax"]}
+        ]))
+        .unwrap();
+        assert!(Some(aggregations.clone()).validate_type());
+        assert_eq!(aggregations[1].name(), "pages");
+        assert_eq!(aggregations[4].columns(), ["user", "price", "tax"]);
+        let json = serde_json::to_value(&aggregations[1]).unwrap();
+        assert_eq!(json["type"], "HLLSketchBuild");
+        assert_eq!(json["lgK"], 14);
+
+        let many_columns: Vec<String> = (0..500).map(|i| format!("m{i}")).collect();
+        for invalid in [
+            json!({"type": "thetaSketch", "name": "users", "fieldName": "user", "size": 1000}),
+            json!({"type": "HLLSketchMerge", "name": "pages", "fieldName": "page", "lgK": 22}),
+            json!({"type": "quantilesDoublesSketch", "name": "latency", "fieldName": "latency_ms", "k": 100}),
+            json!({"type": "arrayOfDoublesSketch", "name": "spend", "fieldName": "user", "metricColumns": ["price"], "numberOfValues": 2}),
+            json!({"type": "arrayOfDoublesSketch", "name": "spend", "fieldName": "user", "metricColumns": many_columns, "numberOfValues": 500}),
+            json!({"type": "arrayOfDoublesSketch", "name": "spend", "fieldName": "user", "metricColumns": many_columns}),
+        ] {
+            let aggregation: Aggregation = serde_json::from_value(invalid.clone()).unwrap();
+            assert!(!aggregation.validate_type(), "{invalid}");
+        }
+
+        let estimate = PostAggregation::Sketch(SketchPostAggregation::ThetaEstimate {
+            name: "both".to_string(),
+            field: Box::new(PostAggregation::Sketch(SketchPostAggregation::ThetaSetOp {
+                name: "intersection".to_string(),
+                func: SetOperation::Intersect,
+                fields: vec![*field_access("users_a"), *field_access("users_b")],
+                size: None,
+            })),
+            error_bounds_std_dev: Some(2),
+        });
+        assert!(estimate.validate_type());
+        let json = serde_json::to_value(&estimate).unwrap();
+        assert_eq!(json["type"], "thetaSketchEstimate");
+        assert_eq!(json["field"]["type"], "thetaSketchSetOp");
+        assert_eq!(json["field"]["func"], "INTERSECT");
+
+        let quantiles = SketchPostAggregation::QuantilesDoublesToQuantiles {
+            name: "p".to_string(),
+            field: field_access("latency"),
+            fractions: vec![0.5, 0.99],
+        };
+        assert!(quantiles.validate_type());
+        let histogram = SketchPostAggregation::QuantilesDoublesToHistogram {
+            name: "h".to_string(),
+            field: field_access("latency"),
+            split_points: Some(vec![10.0, 5.0]),
+            num_bins: None,
+        };
+        assert!(!histogram.validate_type());
+        let not = SketchPostAggregation::ThetaSetOp {
+            name: "only_a".to_string(),
+            func: SetOperation::Not,
+            fields: vec![*field_access("users_a")],
+            size: None,
+        };
+        assert!(!not.validate_type());
+    }
+}
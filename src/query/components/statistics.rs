@@ -0,0 +1,278 @@
+use crate::query::components::model::QueryComponent;
+use crate::query::{FloatingPointNumber, IntegerNumber};
+use serde::{Deserialize, Serialize};
+
+// Aggregators of the druid-stats and druid-histogram extensions
+// https://druid.apache.org/docs/latest/development/extensions-core/stats
+// https://druid.apache.org/docs/latest/development/extensions-core/approximate-histograms
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum StatisticsAggregation {
+    #[serde(rename_all = "camelCase")]
+    Variance {
+        name: String,
+        field_name: String,
+        input_type: Option<VarianceInput>,
+        estimator: Option<Estimator>,
+    },
+
+    // Limits are open when left out, values outside them only count towards the totals
+    #[serde(rename_all = "camelCase")]
+    ApproxHistogram {
+        name: String,
+        field_name: String,
+        resolution: Option<IntegerNumber>,
+        num_buckets: Option<IntegerNumber>,
+        lower_limit: Option<FloatingPointNumber>,
+        upper_limit: Option<FloatingPointNumber>,
+        finalize_as_base64_binary: Option<bool>,
+    },
+
+    // Merges histograms ingested with approxHistogram
+    #[serde(rename_all = "camelCase")]
+    ApproxHistogramFold {
+        name: String,
+        field_name: String,
+        resolution: Option<IntegerNumber>,
+        num_buckets: Option<IntegerNumber>,
+        lower_limit: Option<FloatingPointNumber>,
+        upper_limit: Option<FloatingPointNumber>,
+        finalize_as_base64_binary: Option<bool>,
+    },
+
+    #[serde(rename_all = "camelCase")]
+    FixedBucketsHistogram {
+        name: String,
+        field_name: String,
+        num_buckets: IntegerNumber,
+        lower_limit: FloatingPointNumber,
+        upper_limit: FloatingPointNumber,
+        outlier_handling_mode: Option<OutlierHandlingMode>,
+        finalize_as_base64_binary: Option<bool>,
+    },
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum VarianceInput {
+    Float,
+    Double,
+    Long,
+    // An ingested variance column
+    Variance,
+}
+
+// Druid estimates the sample variance unless asked for the population one
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Estimator {
+    Population,
+    Sample,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum OutlierHandlingMode {
+    Ignore,
+    Overflow,
+    Clip,
+}
+
+// Post aggregators of the same extensions, field_name is the aggregator they read
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum StatisticsPostAggregation {
+    #[serde(rename = "stddev", rename_all = "camelCase")]
+    StandardDeviation {
+        name: String,
+        field_name: String,
+        estimator: Option<Estimator>,
+    },
+
+    // Buckets of bucket_size, the first starting at offset
+    #[serde(rename_all = "camelCase")]
+    Buckets {
+        name: String,
+        field_name: String,
+        bucket_size: FloatingPointNumber,
+        offset: Option<FloatingPointNumber>,
+    },
+
+    #[serde(rename_all = "camelCase")]
+    Quantile {
+        name: String,
+        field_name: String,
+        probability: FloatingPointNumber,
+    },
+
+    #[serde(rename_all = "camelCase")]
+    Quantiles {
+        name: String,
+        field_name: String,
+        probabilities: Vec<FloatingPointNumber>,
+    },
+
+    #[serde(rename_all = "camelCase")]
+    Min { name: String, field_name: String },
+
+    #[serde(rename_all = "camelCase")]
+    Max { name: String, field_name: String },
+}
+
+fn valid_limits(lower: Option<FloatingPointNumber>, upper: Option<FloatingPointNumber>) -> bool {
+    match (lower, upper) {
+        (Some(lower), Some(upper)) => lower < upper,
+        (lower, upper) => [lower, upper]
+            .into_iter()
+            .flatten()
+            .all(|limit| !limit.is_nan()),
+    }
+}
+
+fn probability(value: &FloatingPointNumber) -> bool {
+    (0.0..=1.0).contains(value)
+}
+
+impl StatisticsAggregation {
+    pub fn name(&self) -> &str {
+        match self {
+            StatisticsAggregation::Variance { name, .. }
+            | StatisticsAggregation::ApproxHistogram { name, .. }
+            | StatisticsAggregation::ApproxHistogramFold { name, .. }
+            | StatisticsAggregation::FixedBucketsHistogram { name, .. } => name,
+        }
+    }
+}
+
+impl QueryComponent for StatisticsAggregation {
+    fn validate_type(&self) -> bool {
+        match self {
+            StatisticsAggregation::Variance { .. } => true,
+            StatisticsAggregation::ApproxHistogram {
+                resolution,
+                num_buckets,
+                lower_limit,
+                upper_limit,
+                ..
+            }
+            | StatisticsAggregation::ApproxHistogramFold {
+                resolution,
+                num_buckets,
+                lower_limit,
+                upper_limit,
+                ..
+            } => {
+                resolution.is_none_or(|resolution| resolution > 0)
+                    && num_buckets.is_none_or(|buckets| buckets >= 2)
+                    && valid_limits(*lower_limit, *upper_limit)
+            }
+            StatisticsAggregation::FixedBucketsHistogram {
+                num_buckets,
+                lower_limit,
+                upper_limit,
+                ..
+            } => *num_buckets > 0 && valid_limits(Some(*lower_limit), Some(*upper_limit)),
+        }
+    }
+
+    fn columns(&self) -> Vec<&str> {
+        match self {
+            StatisticsAggregation::Variance { field_name, .. }
+            | StatisticsAggregation::ApproxHistogram { field_name, .. }
+            | StatisticsAggregation::ApproxHistogramFold { field_name, .. }
+            | StatisticsAggregation::FixedBucketsHistogram { field_name, .. } => vec![field_name],
+        }
+    }
+}
+
+impl StatisticsPostAggregation {
+    pub fn name(&self) -> &str {
+        match self {
+            StatisticsPostAggregation::StandardDeviation { name, .. }
+            | StatisticsPostAggregation::Buckets { name, .. }
+            | StatisticsPostAggregation::Quantile { name, .. }
+            | StatisticsPostAggregation::Quantiles { name, .. }
+            | StatisticsPostAggregation::Min { name, .. }
+            | StatisticsPostAggregation::Max { name, .. } => name,
+        }
+    }
+
+    pub(crate) fn name_mut(&mut self) -> &mut String {
+        match self {
+            StatisticsPostAggregation::StandardDeviation { name, .. }
+            | StatisticsPostAggregation::Buckets { name, .. }
+            | StatisticsPostAggregation::Quantile { name, .. }
+            | StatisticsPostAggregation::Quantiles { name, .. }
+            | StatisticsPostAggregation::Min { name, .. }
+            | StatisticsPostAggregation::Max { name, .. } => name,
+        }
+    }
+}
+
+impl QueryComponent for StatisticsPostAggregation {
+    fn validate_type(&self) -> bool {
+        match self {
+            StatisticsPostAggregation::StandardDeviation { .. } => true,
+            StatisticsPostAggregation::Buckets {
+                bucket_size,
+                offset,
+                ..
+            } => *bucket_size > 0.0 && offset.is_none_or(|offset| offset.is_finite()),
+            StatisticsPostAggregation::Quantile { probability: p, .. } => probability(p),
+            StatisticsPostAggregation::Quantiles { probabilities, .. } => {
+                !probabilities.is_empty() && probabilities.iter().all(probability)
+            }
+            StatisticsPostAggregation::Min { .. } | StatisticsPostAggregation::Max { .. } => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::query::{Aggregation, PostAggregation};
+    use serde_json::json;
+
+    #[test]
+    fn test_statistics() {
+        let aggregations: Vec<Aggregation> = serde_json::from_value(json!([
+            {"type": "variance", "name": "var", "fieldName": "latency", "inputType": "double", "estimator": "population"},
+            {"type": "approxHistogram", "name": "hist", "fieldName": "latency", "resolution": 100, "lowerLimit": 0.0},
+            {"type": "approxHistogramFold", "name": "folded", "fieldName": "latency_hist"},
+            {"type": "fixedBucketsHistogram", "name": "fixed", "fieldName": "latency", "numBuckets": 20, "lowerLimit": 0.0, "upperLimit": 1000.0, "outlierHandlingMode": "overflow"}
+        ]))
+        .unwrap();
+        assert!(Some(aggregations.clone()).validate_type());
+        assert_eq!(aggregations[3].name(), "fixed");
+        assert_eq!(aggregations[2].columns(), ["latency_hist"]);
+        let json = serde_json::to_value(&aggregations[3]).unwrap();
+        assert_eq!(json["type"], "fixedBucketsHistogram");
+        assert_eq!(json["outlierHandlingMode"], "overflow");
+
+        let inverted: Aggregation = serde_json::from_value(json!(
+            {"type": "fixedBucketsHistogram", "name": "fixed", "fieldName": "latency", "numBuckets": 20, "lowerLimit": 10.0, "upperLimit": 1.0}
+        ))
+        .unwrap();
+        assert!(!inverted.validate_type());
+
+        let post_aggregations: Option<Vec<PostAggregation>> = serde_json::from_value(json!([
+            {"type": "stddev", "name": "sd", "fieldName": "var", "estimator": "population"},
+            {"type": "buckets", "name": "b", "fieldName": "hist", "bucketSize": 50.0, "offset": 0.0},
+            {"type": "quantile", "name": "p99", "fieldName": "hist", "probability": 0.99},
+            {"type": "quantiles", "name": "ps", "fieldName": "fixed", "probabilities": [0.5, 0.9]},
+            {"type": "min", "name": "lowest", "fieldName": "hist"},
+            {"type": "max", "name": "highest", "fieldName": "hist"}
+        ]))
+        .unwrap();
+        assert!(post_aggregations.validate_type());
+        let json = serde_json::to_value(&post_aggregations.unwrap()[0]).unwrap();
+        assert_eq!(json["type"], "stddev");
+
+        let quantile = StatisticsPostAggregation::Quantile {
+            name: "p".to_string(),
+            field_name: "hist".to_string(),
+            probability: 1.5,
+        };
+        assert!(!quantile.validate_type());
+    }
+}
@@ -13,8 +13,8 @@ impl QueryComponent for ToInclude {
     fn validate_type(&self) -> bool {
         match self {
             //TODO
-            ToInclude::All { .. } => true,
-            ToInclude::None { .. } => true,
+            ToInclude::All => true,
+            ToInclude::None => true,
             ToInclude::List { .. } => true,
         }
     }
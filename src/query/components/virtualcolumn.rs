@@ -1,18 +1,192 @@
 use crate::query::components::model::QueryComponent;
-use crate::query::{Expression, OutputType};
+use crate::query::{DruidVersion, Expression, OutputType, UnsupportedFeature};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
+pub const TIME_COLUMN: &str = "__time";
+
+// https://druid.apache.org/docs/latest/querying/virtual-columns
 #[derive(Debug, Clone, Deserialize, Serialize)]
-#[serde(tag = "type", rename = "camelCase")]
-pub struct VirtualColumn {
-    name: String,
-    expression: Expression,
-    output_type: OutputType,
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum VirtualColumn {
+    #[serde(rename_all = "camelCase")]
+    Expression {
+        name: String,
+        expression: Expression,
+        output_type: Option<OutputType>,
+    },
+
+    // Reads a path such as $.a.b out of a nested (COMPLEX<json>) column
+    #[serde(rename = "nested-field", rename_all = "camelCase")]
+    NestedField {
+        column_name: String,
+        output_name: String,
+        expected_type: Option<OutputType>,
+        path: Option<String>,
+        process_from_raw: Option<bool>,
+    },
+
+    // Keeps only the listed values of a multi-value column, or all but them
+    #[serde(rename = "mv-filtered", rename_all = "camelCase")]
+    MvFiltered {
+        name: String,
+        delegate: String,
+        values: Vec<String>,
+        is_allow_list: Option<bool>,
+    },
+}
+
+impl VirtualColumn {
+    pub fn expression(name: impl Into<String>, expression: Expression) -> Self {
+        VirtualColumn::Expression {
+            name: name.into(),
+            expression,
+            output_type: None,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            VirtualColumn::Expression { name, .. } => name,
+            VirtualColumn::NestedField { output_name, .. } => output_name,
+            VirtualColumn::MvFiltered { name, .. } => name,
+        }
+    }
 }
 
 impl QueryComponent for VirtualColumn {
     fn validate_type(&self) -> bool {
-        //TODO
-        true
+        let name = self.name();
+        if name.is_empty() || name == TIME_COLUMN {
+            return false;
+        }
+        match self {
+            VirtualColumn::Expression { expression, .. } => expression.validate_type(),
+            VirtualColumn::NestedField {
+                column_name, path, ..
+            } => !column_name.is_empty() && path.as_ref().is_none_or(|path| path.starts_with('$')),
+            VirtualColumn::MvFiltered { delegate, .. } => !delegate.is_empty(),
+        }
+    }
+
+    fn adapt(&mut self, version: &DruidVersion) -> Result<(), UnsupportedFeature> {
+        match self {
+            VirtualColumn::Expression { .. } => Ok(()),
+            VirtualColumn::NestedField { .. } => {
+                version.require("nested-field virtual column", DruidVersion::NESTED_COLUMNS)
+            }
+            VirtualColumn::MvFiltered { .. } => version.require(
+                "mv-filtered virtual column",
+                DruidVersion::LIST_FILTERED_VIRTUAL_COLUMN,
+            ),
+        }
+    }
+
+    fn columns(&self) -> Vec<&str> {
+        match self {
+            VirtualColumn::Expression { expression, .. } => expression.columns(),
+            VirtualColumn::NestedField { column_name, .. } => vec![column_name],
+            VirtualColumn::MvFiltered { delegate, .. } => vec![delegate],
+        }
+    }
+}
+
+// Names are unique within a query and virtual columns may read each other, but not in a cycle
+impl QueryComponent for Option<Vec<VirtualColumn>> {
+    fn validate_type(&self) -> bool {
+        let Some(virtual_columns) = self else {
+            return true;
+        };
+        let mut by_name = HashMap::new();
+        for virtual_column in virtual_columns {
+            if !virtual_column.validate_type()
+                || by_name
+                    .insert(virtual_column.name(), virtual_column)
+                    .is_some()
+            {
+                return false;
+            }
+        }
+
+        // Depth first, a column met again while still on the path closes a cycle
+        fn visit<'a>(
+            name: &'a str,
+            by_name: &HashMap<&'a str, &'a VirtualColumn>,
+            path: &mut Vec<&'a str>,
+            done: &mut Vec<&'a str>,
+        ) -> bool {
+            if done.contains(&name) {
+                return true;
+            }
+            if path.contains(&name) {
+                return false;
+            }
+            let Some(virtual_column) = by_name.get(name) else {
+                return true;
+            };
+            path.push(name);
+            let acyclic = virtual_column
+                .columns()
+                .into_iter()
+                .all(|input| visit(input, by_name, path, done));
+            path.pop();
+            done.push(name);
+            acyclic
+        }
+        let mut done = vec![];
+        virtual_columns
+            .iter()
+            .all(|virtual_column| visit(virtual_column.name(), &by_name, &mut vec![], &mut done))
+    }
+
+    fn adapt(&mut self, version: &DruidVersion) -> Result<(), UnsupportedFeature> {
+        self.iter_mut()
+            .flatten()
+            .try_for_each(|virtual_column| virtual_column.adapt(version))
+    }
+
+    fn columns(&self) -> Vec<&str> {
+        self.iter()
+            .flatten()
+            .flat_map(|virtual_column| virtual_column.columns())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_virtual_columns() {
+        let columns: Vec<VirtualColumn> = serde_json::from_value(json!([
+            {"type": "expression", "name": "v0", "expression": "concat(v1, '-', country)", "outputType": "STRING"},
+            {"type": "nested-field", "columnName": "attrs", "outputName": "v1", "path": "$.os", "expectedType": "STRING"},
+            {"type": "mv-filtered", "name": "v2", "delegate": "tags", "values": ["a"], "isAllowList": true}
+        ]))
+        .unwrap();
+        assert_eq!(
+            columns.iter().map(VirtualColumn::name).collect::<Vec<_>>(),
+            ["v0", "v1", "v2"]
+        );
+        assert_eq!(columns[0].columns(), ["v1", "country"]);
+        let json = serde_json::to_value(&columns[1]).unwrap();
+        assert_eq!(json["type"], "nested-field");
+        assert_eq!(json["outputName"], "v1");
+        assert!(Some(columns.clone()).validate_type());
+
+        let mut duplicate = columns.clone();
+        duplicate.push(VirtualColumn::expression("v2", Expression::Long(1)));
+        assert!(!Some(duplicate).validate_type());
+
+        let cycle = vec![
+            VirtualColumn::expression("a", Expression::column("b") + 1),
+            VirtualColumn::expression("b", Expression::column("c") + 1),
+            VirtualColumn::expression("c", Expression::column("a") + 1),
+        ];
+        assert!(!Some(cycle).validate_type());
+        let time = VirtualColumn::expression(TIME_COLUMN, Expression::Long(0));
+        assert!(!time.validate_type());
     }
 }
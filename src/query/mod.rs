@@ -1,5 +1,13 @@
 mod components;
 mod model;
+mod queries;
+mod result;
+mod sql;
+mod version;
 
 pub use components::*;
 pub use model::*;
+pub use queries::*;
+pub use result::*;
+pub use sql::*;
+pub use version::*;
@@ -1,17 +1,57 @@
-use crate::query::components::*;
-use serde::{Deserialize, Serialize};
+use crate::query::queries::*;
+use crate::query::{
+    Context, DataSource, DruidVersion, QueryResult, TIME_COLUMN, UnsupportedFeature, VirtualColumn,
+};
+use serde::{Deserialize, Deserializer, Serialize};
 
-// Query definitions in this file apply to Druid v0.22.1
-// There may or may not be breaking API changes between this version and latest
-// https://druid.apache.org/docs/0.22.1/querying/querying.html
+// Query definitions in this file started out from Druid v0.22.1, components added since then are
+// rewritten or rejected by adapt() for clusters that predate them
+// https://druid.apache.org/docs/latest/querying/querying.html
 
 // Int and Float types can probably be smaller, but I don't know for sure yet
 pub type IntegerNumber = u64;
 pub type FloatingPointNumber = f64;
 
-pub trait TypeConstrainedQuery: Serialize + for<'a> Deserialize<'a> {
+pub trait TypeConstrainedQuery:
+    Serialize + for<'a> Deserialize<'a> + Clone + Into<NativeQuery> + Send + Sync
+{
+    type Response: Send;
+
+    fn query_type(&self) -> NativeQueryType;
+    fn context_mut(&mut self) -> &mut Option<Context>;
+    fn data_source(&self) -> &DataSource;
     fn validate_type(&self) -> bool;
     fn validate_subcomponents(&self) -> bool;
+    // Called before validation with the version of the cluster the query is sent to. Walks every
+    // component, including granularities and dimension specs, which are all version-neutral so far.
+    fn adapt(&mut self, version: &DruidVersion) -> Result<(), UnsupportedFeature>;
+    fn parse_response(&self, body: &str) -> Result<Self::Response, serde_json::Error>;
+
+    // Columns read by dimensions, filters, aggregations and virtual columns
+    fn columns(&self) -> Vec<&str> {
+        vec![]
+    }
+    fn virtual_columns(&self) -> &[VirtualColumn] {
+        &[]
+    }
+    // Referenced columns that are neither virtual columns of the query nor among `known`,
+    // e.g. the dimensions and metrics of CathbadClient::datasource
+    fn unresolved_columns<'a>(&'a self, known: &[String]) -> Vec<&'a str> {
+        let virtual_columns = self.virtual_columns();
+        let mut unresolved = vec![];
+        for column in self.columns() {
+            if column != TIME_COLUMN
+                && !known.iter().any(|known| known == column)
+                && !virtual_columns
+                    .iter()
+                    .any(|virtual_column| virtual_column.name() == column)
+                && !unresolved.contains(&column)
+            {
+                unresolved.push(column);
+            }
+        }
+        unresolved
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq)]
@@ -22,222 +62,179 @@ pub enum NativeQueryType {
     GroupBy,
     TimeBoundary,
     SegmentMetadata,
+    #[serde(rename = "dataSourceMetadata")]
     DatasourceMetadata,
     Scan,
     Search,
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
-#[serde(tag = "queryType", rename_all = "camelCase")]
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)] // each query struct writes its own queryType tag
 pub enum NativeQuery {
-    #[serde(rename_all = "camelCase")]
-    Timeseries {
-        data_source: DataSource,
-        descending: Option<bool>,
-        intervals: Vec<Interval>,
-        granularity: Granularity,
-        filter: Option<Filter>,
-        aggregations: Option<Aggregation>,
-        post_aggregations: Option<Vec<PostAggregation>>,
-        limit: Option<IntegerNumber>,
-        context: Option<Context>,
-    },
-
-    #[serde(rename_all = "camelCase")]
-    TopN {
-        data_source: DataSource,
-        intervals: Vec<Interval>,
-        granularity: Granularity,
-        filter: Option<Filter>,
-        aggregations: Option<Aggregation>,
-        post_aggregations: Option<Vec<PostAggregation>>,
-        dimension: DimensionSpec,
-        threshold: IntegerNumber,
-        metric: TopNMetricSpec,
-        context: Option<Context>,
-    },
-
-    #[serde(rename_all = "camelCase")]
-    GroupBy {
-        data_source: DataSource,
-        dimensions: Vec<DimensionSpec>,
-        limit_spec: Option<LimitSpec>,
-        having: Option<Having>,
-        granularity: Granularity,
-        filter: Option<Filter>,
-        aggregations: Option<Aggregation>,
-        post_aggregations: Option<Vec<PostAggregation>>,
-        intervals: Vec<String>,
-        subtotals_spec: Option<Vec<Vec<String>>>, // DESGUSTANG, but that's the spec
-        context: Option<Context>,
-    },
-
-    #[serde(rename_all = "camelCase")]
-    TimeBoundary {
-        data_source: DataSource,
-        bound: Option<Bound>,
-        filter: Option<Filter>,
-        context: Option<Context>,
-    },
-
-    #[serde(rename_all = "camelCase")]
-    SegmentMetadata {
-        data_source: DataSource,
-        intervals: Option<Vec<Interval>>,
-        to_include: Option<Vec<ToInclude>>,
-        merge: Option<bool>,
-        context: Option<Context>,
-        analysis_types: Vec<AnalysisType>,
-        lenient_aggregator_merge: Option<bool>,
-    },
-
-    #[serde(rename_all = "camelCase")]
-    DatasourceMetadata {
-        data_source: DataSource,
-        context: Option<Context>,
-    },
-
-    #[serde(rename_all = "camelCase")]
-    Scan {
-        data_source: DataSource,
-        intervals: Vec<Interval>,
-        result_format: Option<ResultFormat>,
-        offset: Option<IntegerNumber>,
-        order: Option<Order>,
-        legacy: Option<bool>,
-        context: Option<Context>,
-    },
-
-    #[serde(rename_all = "camelCase")]
-    Search {
-        data_source: DataSource,
-        granularity: Option<Granularity>,
-        filter: Option<Filter>,
-        limit: Option<IntegerNumber>,
-        intervals: Vec<Interval>,
-        search_dimensions: Option<Vec<String>>,
-        query: SearchQuery,
-        sort: Option<Sort>,
-        context: Option<Context>,
-    },
+    Timeseries(TimeseriesQuery),
+    TopN(TopNQuery),
+    GroupBy(GroupByQuery),
+    TimeBoundary(TimeBoundaryQuery),
+    SegmentMetadata(SegmentMetadataQuery),
+    DatasourceMetadata(DatasourceMetadataQuery),
+    Scan(ScanQuery),
+    Search(SearchQuery),
+}
+
+impl<'de> Deserialize<'de> for NativeQuery {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        // Tagged structs don't check their tag on the way in, so dispatch on it here
+        #[derive(Deserialize)]
+        #[serde(tag = "queryType", rename_all = "camelCase")]
+        enum TaggedNativeQuery {
+            Timeseries(TimeseriesQuery),
+            TopN(TopNQuery),
+            GroupBy(GroupByQuery),
+            TimeBoundary(TimeBoundaryQuery),
+            SegmentMetadata(SegmentMetadataQuery),
+            #[serde(rename = "dataSourceMetadata")]
+            DatasourceMetadata(DatasourceMetadataQuery),
+            Scan(ScanQuery),
+            Search(SearchQuery),
+        }
+
+        Ok(match TaggedNativeQuery::deserialize(deserializer)? {
+            TaggedNativeQuery::Timeseries(query) => NativeQuery::Timeseries(query),
+            TaggedNativeQuery::TopN(query) => NativeQuery::TopN(query),
+            TaggedNativeQuery::GroupBy(query) => NativeQuery::GroupBy(query),
+            TaggedNativeQuery::TimeBoundary(query) => NativeQuery::TimeBoundary(query),
+            TaggedNativeQuery::SegmentMetadata(query) => NativeQuery::SegmentMetadata(query),
+            TaggedNativeQuery::DatasourceMetadata(query) => NativeQuery::DatasourceMetadata(query),
+            TaggedNativeQuery::Scan(query) => NativeQuery::Scan(query),
+            TaggedNativeQuery::Search(query) => NativeQuery::Search(query),
+        })
+    }
 }
 
 impl TypeConstrainedQuery for NativeQuery {
+    type Response = QueryResult;
+
+    fn query_type(&self) -> NativeQueryType {
+        match self {
+            NativeQuery::Timeseries(query) => query.query_type(),
+            NativeQuery::TopN(query) => query.query_type(),
+            NativeQuery::GroupBy(query) => query.query_type(),
+            NativeQuery::TimeBoundary(query) => query.query_type(),
+            NativeQuery::SegmentMetadata(query) => query.query_type(),
+            NativeQuery::DatasourceMetadata(query) => query.query_type(),
+            NativeQuery::Scan(query) => query.query_type(),
+            NativeQuery::Search(query) => query.query_type(),
+        }
+    }
+    fn context_mut(&mut self) -> &mut Option<Context> {
+        match self {
+            NativeQuery::Timeseries(query) => query.context_mut(),
+            NativeQuery::TopN(query) => query.context_mut(),
+            NativeQuery::GroupBy(query) => query.context_mut(),
+            NativeQuery::TimeBoundary(query) => query.context_mut(),
+            NativeQuery::SegmentMetadata(query) => query.context_mut(),
+            NativeQuery::DatasourceMetadata(query) => query.context_mut(),
+            NativeQuery::Scan(query) => query.context_mut(),
+            NativeQuery::Search(query) => query.context_mut(),
+        }
+    }
+    fn data_source(&self) -> &DataSource {
+        match self {
+            NativeQuery::Timeseries(query) => query.data_source(),
+            NativeQuery::TopN(query) => query.data_source(),
+            NativeQuery::GroupBy(query) => query.data_source(),
+            NativeQuery::TimeBoundary(query) => query.data_source(),
+            NativeQuery::SegmentMetadata(query) => query.data_source(),
+            NativeQuery::DatasourceMetadata(query) => query.data_source(),
+            NativeQuery::Scan(query) => query.data_source(),
+            NativeQuery::Search(query) => query.data_source(),
+        }
+    }
     fn validate_type(&self) -> bool {
         match self {
-            // TODO
-            NativeQuery::Timeseries { .. } => true,
-            NativeQuery::TopN { .. } => true,
-            NativeQuery::GroupBy { .. } => true,
-            NativeQuery::TimeBoundary { .. } => true,
-            NativeQuery::SegmentMetadata { .. } => true,
-            NativeQuery::DatasourceMetadata { .. } => true,
-            NativeQuery::Scan { .. } => true,
-            NativeQuery::Search { .. } => true,
+            NativeQuery::Timeseries(query) => query.validate_type(),
+            NativeQuery::TopN(query) => query.validate_type(),
+            NativeQuery::GroupBy(query) => query.validate_type(),
+            NativeQuery::TimeBoundary(query) => query.validate_type(),
+            NativeQuery::SegmentMetadata(query) => query.validate_type(),
+            NativeQuery::DatasourceMetadata(query) => query.validate_type(),
+            NativeQuery::Scan(query) => query.validate_type(),
+            NativeQuery::Search(query) => query.validate_type(),
         }
     }
     fn validate_subcomponents(&self) -> bool {
         match self {
-            NativeQuery::Timeseries {
-                data_source,
-                filter,
-                aggregations,
-                post_aggregations,
-                ..
-            } => {
-                data_source.validate_type()
-                    && filter.validate_type()
-                    && aggregations.validate_type()
-                    && post_aggregations.validate_type()
-            }
-            NativeQuery::TopN {
-                data_source,
-                filter,
-                aggregations,
-                post_aggregations,
-                dimension,
-                metric,
-                ..
-            } => {
-                data_source.validate_type()
-                    && filter.validate_type()
-                    && aggregations.validate_type()
-                    && post_aggregations.validate_type()
-                    && dimension.validate_type()
-                    && metric.validate_type()
-            }
-            NativeQuery::GroupBy {
-                data_source,
-                dimensions,
-                limit_spec,
-                having,
-                filter,
-                aggregations,
-                post_aggregations,
-                ..
-            } => {
-                data_source.validate_type()
-                    && dimensions.validate_type()
-                    && limit_spec.validate_type()
-                    && having.validate_type()
-                    && filter.validate_type()
-                    && aggregations.validate_type()
-                    && post_aggregations.validate_type()
-            }
-            NativeQuery::TimeBoundary {
-                data_source,
-                filter,
-                ..
-            } => data_source.validate_type() && filter.validate_type(),
-            NativeQuery::SegmentMetadata {
-                data_source,
-                to_include,
-                ..
-            } => data_source.validate_type() && to_include.validate_type(),
-            NativeQuery::DatasourceMetadata { data_source, .. } => data_source.validate_type(),
-            NativeQuery::Scan { data_source, .. } => data_source.validate_type(),
-            NativeQuery::Search {
-                data_source,
-                filter,
-                query,
-                ..
-            } => data_source.validate_type() && filter.validate_type() && query.validate_type(),
+            NativeQuery::Timeseries(query) => query.validate_subcomponents(),
+            NativeQuery::TopN(query) => query.validate_subcomponents(),
+            NativeQuery::GroupBy(query) => query.validate_subcomponents(),
+            NativeQuery::TimeBoundary(query) => query.validate_subcomponents(),
+            NativeQuery::SegmentMetadata(query) => query.validate_subcomponents(),
+            NativeQuery::DatasourceMetadata(query) => query.validate_subcomponents(),
+            NativeQuery::Scan(query) => query.validate_subcomponents(),
+            NativeQuery::Search(query) => query.validate_subcomponents(),
+        }
+    }
+    fn adapt(&mut self, version: &DruidVersion) -> Result<(), UnsupportedFeature> {
+        match self {
+            NativeQuery::Timeseries(query) => query.adapt(version),
+            NativeQuery::TopN(query) => query.adapt(version),
+            NativeQuery::GroupBy(query) => query.adapt(version),
+            NativeQuery::TimeBoundary(query) => query.adapt(version),
+            NativeQuery::SegmentMetadata(query) => query.adapt(version),
+            NativeQuery::DatasourceMetadata(query) => query.adapt(version),
+            NativeQuery::Scan(query) => query.adapt(version),
+            NativeQuery::Search(query) => query.adapt(version),
+        }
+    }
+    fn parse_response(&self, body: &str) -> Result<Self::Response, serde_json::Error> {
+        QueryResult::from_response(&self.query_type(), body)
+    }
+    fn virtual_columns(&self) -> &[VirtualColumn] {
+        match self {
+            NativeQuery::Timeseries(query) => query.virtual_columns(),
+            NativeQuery::TopN(query) => query.virtual_columns(),
+            NativeQuery::GroupBy(query) => query.virtual_columns(),
+            NativeQuery::TimeBoundary(query) => query.virtual_columns(),
+            NativeQuery::SegmentMetadata(query) => query.virtual_columns(),
+            NativeQuery::DatasourceMetadata(query) => query.virtual_columns(),
+            NativeQuery::Scan(query) => query.virtual_columns(),
+            NativeQuery::Search(query) => query.virtual_columns(),
+        }
+    }
+    fn columns(&self) -> Vec<&str> {
+        match self {
+            NativeQuery::Timeseries(query) => query.columns(),
+            NativeQuery::TopN(query) => query.columns(),
+            NativeQuery::GroupBy(query) => query.columns(),
+            NativeQuery::TimeBoundary(query) => query.columns(),
+            NativeQuery::SegmentMetadata(query) => query.columns(),
+            NativeQuery::DatasourceMetadata(query) => query.columns(),
+            NativeQuery::Scan(query) => query.columns(),
+            NativeQuery::Search(query) => query.columns(),
         }
     }
-}
-
-#[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq)]
-pub enum DruidQueryResponse {
-    Success {
-        // TODO
-    },
-    #[serde(untagged)]
-    Error {
-        error: String,
-        error_message: String,
-        error_class: String,
-        host: String,
-    },
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::query::SearchQuerySpec;
     #[test]
     fn test_basic_serde() {
-        let query_basic = NativeQuery::Search {
+        let query_basic = NativeQuery::Search(SearchQuery {
             data_source: DataSource::String("example".to_string()),
             granularity: None,
             filter: None,
+            virtual_columns: None,
             limit: None,
             intervals: vec![],
             search_dimensions: None,
-            query: SearchQuery::InsensitiveContains {
+            query: SearchQuerySpec::InsensitiveContains {
                 value: "DUMMY_VALUE".to_string(),
             },
             sort: None,
             context: None,
-        };
+        });
 
         assert!(query_basic.validate_type());
         assert!(query_basic.validate_subcomponents());
@@ -250,4 +247,45 @@ mod tests {
 
         println!("{:?}", roundabout);
     }
+
+    #[test]
+    fn test_query_type_tagging() {
+        let query = DatasourceMetadataQuery {
+            data_source: DataSource::String("example".to_string()),
+            context: None,
+        };
+        let payload = serde_json::to_value(&query).unwrap();
+        assert_eq!(payload["queryType"], "dataSourceMetadata");
+
+        let native: NativeQuery = serde_json::from_value(payload).unwrap();
+        assert!(matches!(native, NativeQuery::DatasourceMetadata(_)));
+        assert_eq!(native.query_type(), NativeQueryType::DatasourceMetadata);
+    }
+
+    #[test]
+    fn test_unresolved_columns() {
+        let query: NativeQuery = serde_json::from_value(serde_json::json!({
+            "queryType": "groupBy",
+            "dataSource": "wikipedia",
+            "intervals": ["2016-06-27/2016-06-28"],
+            "granularity": "all",
+            "virtualColumns": [
+                {"type": "expression", "name": "hour", "expression": "timestamp_floor(__time, 'PT1H')"},
+                {"type": "nested-field", "columnName": "attrs", "outputName": "os", "path": "$.os"}
+            ],
+            "dimensions": [
+                {"type": "default", "dimension": "hour"},
+                {"type": "default", "dimension": "os"},
+                {"type": "default", "dimension": "browser"}
+            ],
+            "filter": {"type": "selector", "dimension": "channel", "value": "#en.wikipedia"},
+            "aggregations": [{"type": "longSum", "name": "added", "fieldName": "added"}]
+        }))
+        .unwrap();
+        assert!(query.validate_subcomponents());
+        assert_eq!(query.virtual_columns().len(), 2);
+
+        let known = ["channel", "attrs", "added"].map(String::from);
+        assert_eq!(query.unresolved_columns(&known), ["browser"]);
+    }
 }
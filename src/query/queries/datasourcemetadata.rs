@@ -0,0 +1,50 @@
+use crate::query::{
+    Context, DataSource, DatasourceMetadataRow, DruidVersion, NativeQuery, NativeQueryType,
+    QueryComponent, TypeConstrainedQuery, UnsupportedFeature,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(
+    tag = "queryType",
+    rename = "dataSourceMetadata",
+    rename_all = "camelCase"
+)]
+pub struct DatasourceMetadataQuery {
+    pub data_source: DataSource,
+    pub context: Option<Context>,
+}
+
+impl TypeConstrainedQuery for DatasourceMetadataQuery {
+    type Response = Vec<DatasourceMetadataRow>;
+
+    fn query_type(&self) -> NativeQueryType {
+        NativeQueryType::DatasourceMetadata
+    }
+    fn context_mut(&mut self) -> &mut Option<Context> {
+        &mut self.context
+    }
+    fn data_source(&self) -> &DataSource {
+        &self.data_source
+    }
+    fn validate_type(&self) -> bool {
+        // TODO
+        true
+    }
+    fn validate_subcomponents(&self) -> bool {
+        self.data_source.validate_type()
+    }
+    fn adapt(&mut self, version: &DruidVersion) -> Result<(), UnsupportedFeature> {
+        self.data_source.adapt(version)?;
+        self.context.adapt(version)
+    }
+    fn parse_response(&self, body: &str) -> Result<Self::Response, serde_json::Error> {
+        serde_json::from_str(body)
+    }
+}
+
+impl From<DatasourceMetadataQuery> for NativeQuery {
+    fn from(value: DatasourceMetadataQuery) -> Self {
+        NativeQuery::DatasourceMetadata(value)
+    }
+}
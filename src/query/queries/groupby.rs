@@ -0,0 +1,82 @@
+use crate::query::{
+    Aggregation, Context, DataSource, DimensionSpec, DruidVersion, Filter, Granularity, GroupByRow,
+    Having, Interval, LimitSpec, NativeQuery, NativeQueryType, PostAggregation, QueryComponent,
+    TypeConstrainedQuery, UnsupportedFeature, VirtualColumn,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "queryType", rename = "groupBy", rename_all = "camelCase")]
+pub struct GroupByQuery {
+    pub data_source: DataSource,
+    pub dimensions: Vec<DimensionSpec>,
+    pub limit_spec: Option<LimitSpec>,
+    pub having: Option<Having>,
+    pub granularity: Granularity,
+    pub filter: Option<Filter>,
+    pub virtual_columns: Option<Vec<VirtualColumn>>,
+    pub aggregations: Option<Vec<Aggregation>>,
+    pub post_aggregations: Option<Vec<PostAggregation>>,
+    pub intervals: Vec<Interval>,
+    pub subtotals_spec: Option<Vec<Vec<String>>>, // DESGUSTANG, but that's the spec
+    pub context: Option<Context>,
+}
+
+impl TypeConstrainedQuery for GroupByQuery {
+    type Response = Vec<GroupByRow>;
+
+    fn query_type(&self) -> NativeQueryType {
+        NativeQueryType::GroupBy
+    }
+    fn context_mut(&mut self) -> &mut Option<Context> {
+        &mut self.context
+    }
+    fn data_source(&self) -> &DataSource {
+        &self.data_source
+    }
+    fn validate_type(&self) -> bool {
+        // TODO
+        true
+    }
+    fn validate_subcomponents(&self) -> bool {
+        self.data_source.validate_type()
+            && self.virtual_columns.validate_type()
+            && self.granularity.validate_type()
+            && self.dimensions.validate_type()
+            && self.limit_spec.validate_type()
+            && self.having.validate_type()
+            && self.filter.validate_type()
+            && self.aggregations.validate_type()
+            && self.post_aggregations.validate_type()
+    }
+    fn adapt(&mut self, version: &DruidVersion) -> Result<(), UnsupportedFeature> {
+        self.data_source.adapt(version)?;
+        self.virtual_columns.adapt(version)?;
+        self.granularity.adapt(version)?;
+        self.dimensions.adapt(version)?;
+        self.having.adapt(version)?;
+        self.filter.adapt(version)?;
+        self.aggregations.adapt(version)?;
+        self.post_aggregations.adapt(version)?;
+        self.context.adapt(version)
+    }
+    fn parse_response(&self, body: &str) -> Result<Self::Response, serde_json::Error> {
+        serde_json::from_str(body)
+    }
+    fn columns(&self) -> Vec<&str> {
+        let mut columns = self.virtual_columns.columns();
+        columns.extend(self.dimensions.columns());
+        columns.extend(self.filter.columns());
+        columns.extend(self.aggregations.columns());
+        columns
+    }
+    fn virtual_columns(&self) -> &[VirtualColumn] {
+        self.virtual_columns.as_deref().unwrap_or_default()
+    }
+}
+
+impl From<GroupByQuery> for NativeQuery {
+    fn from(value: GroupByQuery) -> Self {
+        NativeQuery::GroupBy(value)
+    }
+}
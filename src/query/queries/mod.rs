@@ -0,0 +1,17 @@
+mod datasourcemetadata;
+mod groupby;
+mod scan;
+mod search;
+mod segmentmetadata;
+mod timeboundary;
+mod timeseries;
+mod topn;
+
+pub use datasourcemetadata::*;
+pub use groupby::*;
+pub use scan::*;
+pub use search::*;
+pub use segmentmetadata::*;
+pub use timeboundary::*;
+pub use timeseries::*;
+pub use topn::*;
@@ -0,0 +1,60 @@
+use crate::query::{
+    Context, DataSource, DruidVersion, IntegerNumber, Interval, NativeQuery, NativeQueryType,
+    Order, QueryComponent, ResultFormat, ScanBatch, TypeConstrainedQuery, UnsupportedFeature,
+    VirtualColumn,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "queryType", rename = "scan", rename_all = "camelCase")]
+pub struct ScanQuery {
+    pub data_source: DataSource,
+    pub intervals: Vec<Interval>,
+    pub virtual_columns: Option<Vec<VirtualColumn>>,
+    pub result_format: Option<ResultFormat>,
+    pub offset: Option<IntegerNumber>,
+    pub order: Option<Order>,
+    pub legacy: Option<bool>,
+    pub context: Option<Context>,
+}
+
+impl TypeConstrainedQuery for ScanQuery {
+    type Response = Vec<ScanBatch>;
+
+    fn query_type(&self) -> NativeQueryType {
+        NativeQueryType::Scan
+    }
+    fn context_mut(&mut self) -> &mut Option<Context> {
+        &mut self.context
+    }
+    fn data_source(&self) -> &DataSource {
+        &self.data_source
+    }
+    fn validate_type(&self) -> bool {
+        // TODO
+        true
+    }
+    fn validate_subcomponents(&self) -> bool {
+        self.data_source.validate_type() && self.virtual_columns.validate_type()
+    }
+    fn adapt(&mut self, version: &DruidVersion) -> Result<(), UnsupportedFeature> {
+        self.data_source.adapt(version)?;
+        self.virtual_columns.adapt(version)?;
+        self.context.adapt(version)
+    }
+    fn parse_response(&self, body: &str) -> Result<Self::Response, serde_json::Error> {
+        serde_json::from_str(body)
+    }
+    fn columns(&self) -> Vec<&str> {
+        self.virtual_columns.columns()
+    }
+    fn virtual_columns(&self) -> &[VirtualColumn] {
+        self.virtual_columns.as_deref().unwrap_or_default()
+    }
+}
+
+impl From<ScanQuery> for NativeQuery {
+    fn from(value: ScanQuery) -> Self {
+        NativeQuery::Scan(value)
+    }
+}
@@ -0,0 +1,71 @@
+use crate::query::{
+    Context, DataSource, DruidVersion, Filter, Granularity, IntegerNumber, Interval, NativeQuery,
+    NativeQueryType, QueryComponent, SearchQuerySpec, SearchRow, Sort, TypeConstrainedQuery,
+    UnsupportedFeature, VirtualColumn,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "queryType", rename = "search", rename_all = "camelCase")]
+pub struct SearchQuery {
+    pub data_source: DataSource,
+    pub granularity: Option<Granularity>,
+    pub filter: Option<Filter>,
+    pub virtual_columns: Option<Vec<VirtualColumn>>,
+    pub limit: Option<IntegerNumber>,
+    pub intervals: Vec<Interval>,
+    pub search_dimensions: Option<Vec<String>>,
+    pub query: SearchQuerySpec,
+    pub sort: Option<Sort>,
+    pub context: Option<Context>,
+}
+
+impl TypeConstrainedQuery for SearchQuery {
+    type Response = Vec<SearchRow>;
+
+    fn query_type(&self) -> NativeQueryType {
+        NativeQueryType::Search
+    }
+    fn context_mut(&mut self) -> &mut Option<Context> {
+        &mut self.context
+    }
+    fn data_source(&self) -> &DataSource {
+        &self.data_source
+    }
+    fn validate_type(&self) -> bool {
+        // TODO
+        true
+    }
+    fn validate_subcomponents(&self) -> bool {
+        self.data_source.validate_type()
+            && self.virtual_columns.validate_type()
+            && self.granularity.validate_type()
+            && self.filter.validate_type()
+            && self.query.validate_type()
+    }
+    fn adapt(&mut self, version: &DruidVersion) -> Result<(), UnsupportedFeature> {
+        self.data_source.adapt(version)?;
+        self.virtual_columns.adapt(version)?;
+        self.granularity.adapt(version)?;
+        self.filter.adapt(version)?;
+        self.context.adapt(version)
+    }
+    fn parse_response(&self, body: &str) -> Result<Self::Response, serde_json::Error> {
+        serde_json::from_str(body)
+    }
+    fn columns(&self) -> Vec<&str> {
+        let mut columns = self.virtual_columns.columns();
+        columns.extend(self.filter.columns());
+        columns.extend(self.search_dimensions.iter().flatten().map(String::as_str));
+        columns
+    }
+    fn virtual_columns(&self) -> &[VirtualColumn] {
+        self.virtual_columns.as_deref().unwrap_or_default()
+    }
+}
+
+impl From<SearchQuery> for NativeQuery {
+    fn from(value: SearchQuery) -> Self {
+        NativeQuery::Search(value)
+    }
+}
@@ -0,0 +1,55 @@
+use crate::query::{
+    AnalysisType, Context, DataSource, DruidVersion, Interval, NativeQuery, NativeQueryType,
+    QueryComponent, SegmentAnalysis, ToInclude, TypeConstrainedQuery, UnsupportedFeature,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(
+    tag = "queryType",
+    rename = "segmentMetadata",
+    rename_all = "camelCase"
+)]
+pub struct SegmentMetadataQuery {
+    pub data_source: DataSource,
+    pub intervals: Option<Vec<Interval>>,
+    pub to_include: Option<Vec<ToInclude>>,
+    pub merge: Option<bool>,
+    pub context: Option<Context>,
+    pub analysis_types: Vec<AnalysisType>,
+    pub lenient_aggregator_merge: Option<bool>,
+}
+
+impl TypeConstrainedQuery for SegmentMetadataQuery {
+    type Response = Vec<SegmentAnalysis>;
+
+    fn query_type(&self) -> NativeQueryType {
+        NativeQueryType::SegmentMetadata
+    }
+    fn context_mut(&mut self) -> &mut Option<Context> {
+        &mut self.context
+    }
+    fn data_source(&self) -> &DataSource {
+        &self.data_source
+    }
+    fn validate_type(&self) -> bool {
+        // TODO
+        true
+    }
+    fn validate_subcomponents(&self) -> bool {
+        self.data_source.validate_type() && self.to_include.validate_type()
+    }
+    fn adapt(&mut self, version: &DruidVersion) -> Result<(), UnsupportedFeature> {
+        self.data_source.adapt(version)?;
+        self.context.adapt(version)
+    }
+    fn parse_response(&self, body: &str) -> Result<Self::Response, serde_json::Error> {
+        serde_json::from_str(body)
+    }
+}
+
+impl From<SegmentMetadataQuery> for NativeQuery {
+    fn from(value: SegmentMetadataQuery) -> Self {
+        NativeQuery::SegmentMetadata(value)
+    }
+}
@@ -0,0 +1,52 @@
+use crate::query::{
+    Bound, Context, DataSource, DruidVersion, Filter, NativeQuery, NativeQueryType, QueryComponent,
+    TimeBoundaryRow, TypeConstrainedQuery, UnsupportedFeature,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "queryType", rename = "timeBoundary", rename_all = "camelCase")]
+pub struct TimeBoundaryQuery {
+    pub data_source: DataSource,
+    pub bound: Option<Bound>,
+    pub filter: Option<Filter>,
+    pub context: Option<Context>,
+}
+
+impl TypeConstrainedQuery for TimeBoundaryQuery {
+    type Response = Vec<TimeBoundaryRow>;
+
+    fn query_type(&self) -> NativeQueryType {
+        NativeQueryType::TimeBoundary
+    }
+    fn context_mut(&mut self) -> &mut Option<Context> {
+        &mut self.context
+    }
+    fn data_source(&self) -> &DataSource {
+        &self.data_source
+    }
+    fn validate_type(&self) -> bool {
+        // TODO
+        true
+    }
+    fn validate_subcomponents(&self) -> bool {
+        self.data_source.validate_type() && self.filter.validate_type()
+    }
+    fn adapt(&mut self, version: &DruidVersion) -> Result<(), UnsupportedFeature> {
+        self.data_source.adapt(version)?;
+        self.filter.adapt(version)?;
+        self.context.adapt(version)
+    }
+    fn parse_response(&self, body: &str) -> Result<Self::Response, serde_json::Error> {
+        serde_json::from_str(body)
+    }
+    fn columns(&self) -> Vec<&str> {
+        self.filter.columns()
+    }
+}
+
+impl From<TimeBoundaryQuery> for NativeQuery {
+    fn from(value: TimeBoundaryQuery) -> Self {
+        NativeQuery::TimeBoundary(value)
+    }
+}
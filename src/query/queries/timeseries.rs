@@ -0,0 +1,106 @@
+use crate::query::{
+    Aggregation, Context, DataSource, DruidVersion, Filter, GapFill, Granularity, IntegerNumber,
+    Interval, NativeQuery, NativeQueryType, PostAggregation, QueryComponent, TimeseriesRow,
+    TypeConstrainedQuery, UnsupportedFeature, VirtualColumn, fill_gaps,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "queryType", rename = "timeseries", rename_all = "camelCase")]
+pub struct TimeseriesQuery {
+    pub data_source: DataSource,
+    pub descending: Option<bool>,
+    pub intervals: Vec<Interval>,
+    pub granularity: Granularity,
+    pub filter: Option<Filter>,
+    pub virtual_columns: Option<Vec<VirtualColumn>>,
+    pub aggregations: Option<Vec<Aggregation>>,
+    pub post_aggregations: Option<Vec<PostAggregation>>,
+    pub limit: Option<IntegerNumber>,
+    pub context: Option<Context>,
+}
+
+impl TypeConstrainedQuery for TimeseriesQuery {
+    type Response = Vec<TimeseriesRow>;
+
+    fn query_type(&self) -> NativeQueryType {
+        NativeQueryType::Timeseries
+    }
+    fn context_mut(&mut self) -> &mut Option<Context> {
+        &mut self.context
+    }
+    fn data_source(&self) -> &DataSource {
+        &self.data_source
+    }
+    fn validate_type(&self) -> bool {
+        // TODO
+        true
+    }
+    fn validate_subcomponents(&self) -> bool {
+        self.data_source.validate_type()
+            && self.virtual_columns.validate_type()
+            && self.granularity.validate_type()
+            && self.filter.validate_type()
+            && self.aggregations.validate_type()
+            && self.post_aggregations.validate_type()
+    }
+    fn adapt(&mut self, version: &DruidVersion) -> Result<(), UnsupportedFeature> {
+        self.data_source.adapt(version)?;
+        self.virtual_columns.adapt(version)?;
+        self.granularity.adapt(version)?;
+        self.filter.adapt(version)?;
+        self.aggregations.adapt(version)?;
+        self.post_aggregations.adapt(version)?;
+        self.context.adapt(version)
+    }
+    fn parse_response(&self, body: &str) -> Result<Self::Response, serde_json::Error> {
+        serde_json::from_str(body)
+    }
+    fn columns(&self) -> Vec<&str> {
+        let mut columns = self.virtual_columns.columns();
+        columns.extend(self.filter.columns());
+        columns.extend(self.aggregations.columns());
+        columns
+    }
+    fn virtual_columns(&self) -> &[VirtualColumn] {
+        self.virtual_columns.as_deref().unwrap_or_default()
+    }
+}
+
+impl TimeseriesQuery {
+    // For results fetched with skipEmptyBuckets, or from a version that skips them regardless
+    pub fn fill_gaps(
+        &self,
+        rows: Vec<TimeseriesRow>,
+        fill: GapFill,
+    ) -> Result<Vec<TimeseriesRow>, String> {
+        let mut buckets = vec![];
+        for interval in &self.intervals {
+            buckets.extend(self.granularity.buckets(interval)?);
+        }
+        buckets.sort_by_key(|bucket| bucket.start());
+        buckets.dedup_by_key(|bucket| bucket.start());
+        if self.descending == Some(true) {
+            buckets.reverse();
+        }
+        let metrics: Vec<&str> = self
+            .aggregations
+            .iter()
+            .flatten()
+            .map(Aggregation::name)
+            .chain(
+                self.post_aggregations
+                    .iter()
+                    .flatten()
+                    .map(PostAggregation::name),
+            )
+            .collect();
+        fill_gaps(rows, &buckets, &metrics, fill)
+    }
+}
+
+impl From<TimeseriesQuery> for NativeQuery {
+    fn from(value: TimeseriesQuery) -> Self {
+        NativeQuery::Timeseries(value)
+    }
+}
@@ -0,0 +1,336 @@
+use crate::query::{IntegerNumber, Interval, NativeQueryType, format_time, parse_time};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+// Result definitions in this file follow the response formats documented for Druid v0.22.1
+// Row types are generic so callers can deserialize into their own structs, and default to a
+// plain JSON object otherwise
+
+pub type ResultObject = Map<String, Value>;
+
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq)]
+pub struct TimeseriesRow<T = ResultObject> {
+    pub timestamp: String,
+    pub result: T,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq)]
+pub struct TopNRow<T = ResultObject> {
+    pub timestamp: String,
+    pub result: Vec<T>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq)]
+pub struct GroupByRow<T = ResultObject> {
+    pub version: String,
+    pub timestamp: String,
+    pub event: T,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ScanBatch {
+    pub segment_id: String,
+    pub columns: Vec<String>,
+    pub events: ScanEvents,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(untagged)]
+pub enum ScanEvents {
+    // resultFormat: list
+    List(Vec<ResultObject>),
+    // resultFormat: compactedList, values are ordered as in ScanBatch::columns
+    CompactedList(Vec<Vec<Value>>),
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq)]
+pub struct SearchRow {
+    pub timestamp: String,
+    pub result: Vec<SearchHit>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq)]
+pub struct SearchHit {
+    pub dimension: String,
+    pub value: Value,
+    pub count: Option<IntegerNumber>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq)]
+pub struct TimeBoundaryRow {
+    pub timestamp: String,
+    pub result: TimeBoundary,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TimeBoundary {
+    // Only the requested side is present when the query sets a bound
+    pub min_time: Option<String>,
+    pub max_time: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SegmentAnalysis {
+    pub id: String,
+    pub intervals: Option<Vec<Interval>>,
+    pub columns: HashMap<String, ColumnAnalysis>,
+    pub size: Option<IntegerNumber>,
+    pub num_rows: Option<IntegerNumber>,
+    pub aggregators: Option<HashMap<String, Value>>,
+    pub timestamp_spec: Option<Value>,
+    pub query_granularity: Option<Value>,
+    pub rollup: Option<bool>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ColumnAnalysis {
+    #[serde(rename = "type")]
+    pub type_: String,
+    pub type_signature: Option<String>,
+    pub has_multiple_values: bool,
+    pub has_nulls: Option<bool>,
+    pub size: IntegerNumber,
+    pub cardinality: Option<IntegerNumber>,
+    pub min_value: Option<Value>,
+    pub max_value: Option<Value>,
+    pub error_message: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq)]
+pub struct DatasourceMetadataRow {
+    pub timestamp: String,
+    pub result: DatasourceMetadata,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DatasourceMetadata {
+    pub max_ingested_event_time: String,
+}
+
+#[derive(Debug, Clone, Serialize, Eq, PartialEq)]
+#[serde(untagged)]
+pub enum QueryResult {
+    Timeseries(Vec<TimeseriesRow>),
+    TopN(Vec<TopNRow>),
+    GroupBy(Vec<GroupByRow>),
+    TimeBoundary(Vec<TimeBoundaryRow>),
+    SegmentMetadata(Vec<SegmentAnalysis>),
+    DatasourceMetadata(Vec<DatasourceMetadataRow>),
+    Scan(Vec<ScanBatch>),
+    Search(Vec<SearchRow>),
+}
+
+impl QueryResult {
+    // Druid responses don't carry the query type, so the shape has to be picked by the caller
+    pub fn from_response(
+        query_type: &NativeQueryType,
+        body: &str,
+    ) -> Result<Self, serde_json::Error> {
+        Ok(match query_type {
+            NativeQueryType::Timeseries => QueryResult::Timeseries(serde_json::from_str(body)?),
+            NativeQueryType::TopN => QueryResult::TopN(serde_json::from_str(body)?),
+            NativeQueryType::GroupBy => QueryResult::GroupBy(serde_json::from_str(body)?),
+            NativeQueryType::TimeBoundary => QueryResult::TimeBoundary(serde_json::from_str(body)?),
+            NativeQueryType::SegmentMetadata => {
+                QueryResult::SegmentMetadata(serde_json::from_str(body)?)
+            }
+            NativeQueryType::DatasourceMetadata => {
+                QueryResult::DatasourceMetadata(serde_json::from_str(body)?)
+            }
+            NativeQueryType::Scan => QueryResult::Scan(serde_json::from_str(body)?),
+            NativeQueryType::Search => QueryResult::Search(serde_json::from_str(body)?),
+        })
+    }
+}
+
+// How fill_gaps makes up the rows of empty buckets
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GapFill {
+    // Every metric set to 0
+    Zero,
+    Null,
+    // Repeats the row before, nulls until the first row
+    LastValue,
+}
+
+// Puts a row in every bucket, in the order of buckets. Rows that don't start a bucket, or start
+// the same one, are an error, they mean the granularity or time zone doesn't match the query's.
+// Made up rows have the given metrics and those of the other rows.
+pub fn fill_gaps(
+    rows: Vec<TimeseriesRow>,
+    buckets: &[Interval],
+    metrics: &[&str],
+    fill: GapFill,
+) -> Result<Vec<TimeseriesRow>, String> {
+    let mut metrics: Vec<String> = metrics.iter().map(|metric| metric.to_string()).collect();
+    let mut by_start = HashMap::new();
+    for row in rows {
+        for metric in row.result.keys() {
+            if !metrics.contains(metric) {
+                metrics.push(metric.clone());
+            }
+        }
+        let start = parse_time(&row.timestamp)?.timestamp_millis();
+        if by_start.contains_key(&start) {
+            return Err(format!("more than one row at {}", row.timestamp));
+        }
+        by_start.insert(start, row);
+    }
+    let filler = |value: Value| -> ResultObject {
+        metrics
+            .iter()
+            .map(|metric| (metric.clone(), value.clone()))
+            .collect()
+    };
+
+    let mut filled: Vec<TimeseriesRow> = Vec::with_capacity(buckets.len());
+    for bucket in buckets {
+        let row = match by_start.remove(&bucket.start().timestamp_millis()) {
+            Some(row) => row,
+            None => TimeseriesRow {
+                timestamp: format_time(&bucket.start()),
+                result: match (fill, filled.last()) {
+                    (GapFill::Zero, _) => filler(Value::from(0)),
+                    (GapFill::LastValue, Some(last)) => last.result.clone(),
+                    (GapFill::Null | GapFill::LastValue, _) => filler(Value::Null),
+                },
+            },
+        };
+        filled.push(row);
+    }
+    if let Some(row) = by_start.values().next() {
+        return Err(format!("row at {} doesn't start a bucket", row.timestamp));
+    }
+    Ok(filled)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::query::TimeseriesQuery;
+    use serde_json::json;
+
+    #[test]
+    fn test_aggregate_results() {
+        let body = r#"[{"timestamp":"2012-01-01T00:00:00.000Z","result":{"sample_name1":10,"sample_divide":1.5}}]"#;
+        let QueryResult::Timeseries(rows) =
+            QueryResult::from_response(&NativeQueryType::Timeseries, body).unwrap()
+        else {
+            panic!("expected timeseries result");
+        };
+        assert_eq!(rows[0].result["sample_name1"], json!(10));
+
+        let body = r#"[{"timestamp":"2013-08-31T00:00:00.000Z","result":[{"dim1":"a","count":3},{"dim1":"b","count":2}]}]"#;
+        let QueryResult::TopN(rows) =
+            QueryResult::from_response(&NativeQueryType::TopN, body).unwrap()
+        else {
+            panic!("expected topN result");
+        };
+        assert_eq!(rows[0].result.len(), 2);
+
+        let body = r#"[{"version":"v1","timestamp":"2012-01-01T00:00:00.000Z","event":{"country":"US","total_usage":42}}]"#;
+        let QueryResult::GroupBy(rows) =
+            QueryResult::from_response(&NativeQueryType::GroupBy, body).unwrap()
+        else {
+            panic!("expected groupBy result");
+        };
+        assert_eq!(rows[0].event["country"], json!("US"));
+    }
+
+    #[test]
+    fn test_fill_gaps() {
+        let rows: Vec<TimeseriesRow> = serde_json::from_value(json!([
+            {"timestamp": "2024-01-01T00:00:00.000Z", "result": {"edits": 10, "added": 5}},
+            {"timestamp": "2024-01-03T00:00:00.000Z", "result": {"edits": 7, "added": 2}}
+        ]))
+        .unwrap();
+        let buckets: Vec<Interval> = (1..=4)
+            .map(|day| format!("2024-01-0{day}/P1D").parse().unwrap())
+            .collect();
+
+        let zero = fill_gaps(rows.clone(), &buckets, &[], GapFill::Zero).unwrap();
+        assert_eq!(zero.len(), 4);
+        assert_eq!(zero[1].timestamp, "2024-01-02T00:00:00.000Z");
+        assert_eq!(
+            Value::Object(zero[1].result.clone()),
+            json!({"edits": 0, "added": 0})
+        );
+        assert_eq!(zero[2], rows[1]);
+
+        let last = fill_gaps(rows.clone(), &buckets, &[], GapFill::LastValue).unwrap();
+        assert_eq!(last[1].result, rows[0].result);
+        assert_eq!(last[3].result, rows[1].result);
+        // A bucket list that doesn't cover the first row
+        assert!(fill_gaps(rows.clone(), &buckets[1..], &[], GapFill::Null).is_err());
+
+        let nulls = fill_gaps(rows[1..].to_vec(), &buckets, &[], GapFill::LastValue).unwrap();
+        assert_eq!(nulls[0].result["edits"], Value::Null);
+
+        let mut duplicate = rows.clone();
+        duplicate.push(rows[0].clone());
+        assert!(fill_gaps(duplicate, &buckets, &[], GapFill::Zero).is_err());
+
+        // Without any rows the metrics come from the query
+        let query: TimeseriesQuery = serde_json::from_value(json!({
+            "queryType": "timeseries",
+            "dataSource": "wikipedia",
+            "intervals": ["2024-01-01/2024-01-03"],
+            "granularity": "day",
+            "aggregations": [{"type": "longSum", "name": "edits", "fieldName": "count"}],
+            "postAggregations": [{"type": "constant", "name": "one", "value": 1}]
+        }))
+        .unwrap();
+        let empty = query.fill_gaps(vec![], GapFill::Zero).unwrap();
+        assert_eq!(empty.len(), 2);
+        assert_eq!(
+            Value::Object(empty[1].result.clone()),
+            json!({"edits": 0, "one": 0})
+        );
+    }
+
+    #[test]
+    fn test_scan_result_formats() {
+        let list = r#"[{"segmentId":"wikipedia_2013","columns":["__time","page"],"events":[{"__time":1356998400000,"page":"Main"}]}]"#;
+        let compacted = r#"[{"segmentId":"wikipedia_2013","columns":["__time","page"],"events":[[1356998400000,"Main"]]}]"#;
+
+        let batches: Vec<ScanBatch> = serde_json::from_str(list).unwrap();
+        assert!(matches!(batches[0].events, ScanEvents::List(ref events) if events.len() == 1));
+
+        let batches: Vec<ScanBatch> = serde_json::from_str(compacted).unwrap();
+        assert!(
+            matches!(batches[0].events, ScanEvents::CompactedList(ref events) if events[0][1] == json!("Main"))
+        );
+    }
+
+    #[test]
+    fn test_metadata_results() {
+        let body = r#"[{"timestamp":"2013-05-09T18:24:00.000Z","result":{"minTime":"2013-05-09T18:24:00.000Z","maxTime":"2013-05-09T18:37:00.000Z"}}]"#;
+        let rows: Vec<TimeBoundaryRow> = serde_json::from_str(body).unwrap();
+        assert_eq!(
+            rows[0].result.max_time.as_deref(),
+            Some("2013-05-09T18:37:00.000Z")
+        );
+
+        let body = r#"[{"timestamp":"2013-05-09T18:24:00.000Z","result":{"maxIngestedEventTime":"2013-05-09T18:24:09.007Z"}}]"#;
+        let rows: Vec<DatasourceMetadataRow> = serde_json::from_str(body).unwrap();
+        assert_eq!(
+            rows[0].result.max_ingested_event_time,
+            "2013-05-09T18:24:09.007Z"
+        );
+
+        let body = r#"[{"id":"some_id","intervals":["2013-05-13T00:00:00.000Z/2013-05-14T00:00:00.000Z"],
+            "columns":{"__time":{"type":"LONG","hasMultipleValues":false,"size":407240380,"cardinality":null,"errorMessage":null},
+            "dim1":{"type":"STRING","hasMultipleValues":false,"size":100000,"cardinality":1944,"errorMessage":null}},
+            "aggregators":{"metric1":{"type":"longSum","name":"metric1","fieldName":"metric1"}},
+            "queryGranularity":{"type":"none"},"size":300000,"numRows":5000000}]"#;
+        let rows: Vec<SegmentAnalysis> = serde_json::from_str(body).unwrap();
+        assert_eq!(rows[0].columns["dim1"].cardinality, Some(1944));
+        assert_eq!(rows[0].num_rows, Some(5000000));
+    }
+}
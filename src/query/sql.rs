@@ -0,0 +1,207 @@
+use crate::query::{Context, ResultObject};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+// Druid SQL API, see https://druid.apache.org/docs/0.22.1/querying/sql.html#http-post
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SqlQuery {
+    pub query: String,
+    pub result_format: Option<SqlResultFormat>,
+    pub header: Option<bool>,
+    pub types_header: Option<bool>,
+    pub sql_types_header: Option<bool>,
+    pub parameters: Option<Vec<SqlParameter>>,
+    pub context: Option<SqlContext>,
+}
+
+impl SqlQuery {
+    pub fn new(query: impl Into<String>) -> Self {
+        Self {
+            query: query.into(),
+            result_format: None,
+            header: None,
+            types_header: None,
+            sql_types_header: None,
+            parameters: None,
+            context: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum SqlResultFormat {
+    #[default]
+    Object,
+    Array,
+    ObjectLines,
+    ArrayLines,
+    Csv,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum SqlType {
+    Char,
+    Varchar,
+    Decimal,
+    Float,
+    Real,
+    Double,
+    Boolean,
+    Tinyint,
+    Smallint,
+    Integer,
+    Bigint,
+    Timestamp,
+    Date,
+    Other,
+    Array,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SqlParameter {
+    #[serde(rename = "type")]
+    pub type_: SqlType,
+    pub value: Value,
+}
+
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SqlContext {
+    pub sql_query_id: Option<String>,
+    pub sql_time_zone: Option<String>,
+    pub sql_stringify_arrays: Option<bool>,
+    pub use_approximate_count_distinct: Option<bool>,
+    pub use_approximate_top_n: Option<bool>,
+
+    // SQL queries accept the native query context parameters as well
+    #[serde(flatten)]
+    pub native: Context,
+}
+
+// When header, typesHeader or sqlTypesHeader are set, the first row is the header row
+#[derive(Debug, Clone, Serialize, Eq, PartialEq)]
+#[serde(untagged)]
+pub enum SqlResponse {
+    // object, objectLines
+    Objects(Vec<ResultObject>),
+    // array, arrayLines
+    Arrays(Vec<Vec<Value>>),
+    Csv(Vec<Vec<String>>),
+}
+
+impl SqlResponse {
+    pub fn from_response(
+        result_format: &SqlResultFormat,
+        body: &str,
+    ) -> Result<Self, serde_json::Error> {
+        Ok(match result_format {
+            SqlResultFormat::Object => SqlResponse::Objects(serde_json::from_str(body)?),
+            SqlResultFormat::Array => SqlResponse::Arrays(serde_json::from_str(body)?),
+            SqlResultFormat::ObjectLines => SqlResponse::Objects(parse_lines(body)?),
+            SqlResultFormat::ArrayLines => SqlResponse::Arrays(parse_lines(body)?),
+            SqlResultFormat::Csv => SqlResponse::Csv(parse_csv(body)),
+        })
+    }
+}
+
+// The *Lines formats are newline-delimited JSON terminated by an empty line
+fn parse_lines<T: for<'a> Deserialize<'a>>(body: &str) -> Result<Vec<T>, serde_json::Error> {
+    body.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(serde_json::from_str)
+        .collect()
+}
+
+// RFC 4180 style: fields may be quoted, quotes inside quoted fields are doubled
+fn parse_csv(body: &str) -> Vec<Vec<String>> {
+    let mut rows = vec![];
+    let mut row = vec![];
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = body.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => row.push(std::mem::take(&mut field)),
+            '\r' if !quoted => {}
+            '\n' if !quoted => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            }
+            _ => field.push(c),
+        }
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+
+    // Druid terminates the result set with an empty line
+    while rows
+        .last()
+        .is_some_and(|row| row.len() == 1 && row[0].is_empty())
+    {
+        rows.pop();
+    }
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_sql_query_serde() {
+        let mut query = SqlQuery::new("SELECT COUNT(*) FROM wikipedia WHERE channel = ?");
+        query.result_format = Some(SqlResultFormat::ArrayLines);
+        query.parameters = Some(vec![SqlParameter {
+            type_: SqlType::Varchar,
+            value: json!("#en.wikipedia"),
+        }]);
+
+        let payload = serde_json::to_value(&query).unwrap();
+        assert_eq!(payload["resultFormat"], "arrayLines");
+        assert_eq!(payload["parameters"][0]["type"], "VARCHAR");
+    }
+
+    #[test]
+    fn test_sql_result_formats() {
+        let body = "{\"page\":\"Main\",\"cnt\":3}\n{\"page\":\"Other\",\"cnt\":1}\n\n";
+        let SqlResponse::Objects(rows) =
+            SqlResponse::from_response(&SqlResultFormat::ObjectLines, body).unwrap()
+        else {
+            panic!("expected objects");
+        };
+        assert_eq!(rows[1]["cnt"], json!(1));
+
+        let body = "[\"Main\",3]\n[\"Other\",1]\n\n";
+        let response = SqlResponse::from_response(&SqlResultFormat::ArrayLines, body).unwrap();
+        assert_eq!(
+            response,
+            SqlResponse::Arrays(vec![
+                vec![json!("Main"), json!(3)],
+                vec![json!("Other"), json!(1)]
+            ])
+        );
+
+        let body = "page,cnt\n\"Main, \"\"the\"\" page\",3\n\n";
+        let response = SqlResponse::from_response(&SqlResultFormat::Csv, body).unwrap();
+        assert_eq!(
+            response,
+            SqlResponse::Csv(vec![
+                vec!["page".to_string(), "cnt".to_string()],
+                vec!["Main, \"the\" page".to_string(), "3".to_string()],
+            ])
+        );
+    }
+}
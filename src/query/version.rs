@@ -0,0 +1,259 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+// Release of the cluster queries are written for, e.g. "0.22.1" or "31.0.0". Suffixes such as
+// "-SNAPSHOT" or vendor builds are ignored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DruidVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+impl DruidVersion {
+    pub const fn new(major: u32, minor: u32, patch: u32) -> Self {
+        Self {
+            major,
+            minor,
+            patch,
+        }
+    }
+
+    // Typed equality, range and null filters
+    pub const TYPED_FILTERS: DruidVersion = DruidVersion::new(28, 0, 0);
+    // The unnest data source left its experimental flag behind
+    pub const UNNEST: DruidVersion = DruidVersion::new(27, 0, 0);
+    // The groupBy v1 engine was removed
+    pub const GROUP_BY_V1_REMOVED: DruidVersion = DruidVersion::new(28, 0, 0);
+    // Nested (COMPLEX<json>) columns and the nested-field virtual column
+    pub const NESTED_COLUMNS: DruidVersion = DruidVersion::new(24, 0, 0);
+    // The mv-filtered virtual column
+    pub const LIST_FILTERED_VIRTUAL_COLUMN: DruidVersion = DruidVersion::new(0, 21, 0);
+
+    // druid.javascript.enabled became opt-in
+    pub const JAVASCRIPT_DISABLED: DruidVersion = DruidVersion::new(0, 10, 0);
+
+    pub(crate) fn require(
+        &self,
+        feature: &str,
+        since: DruidVersion,
+    ) -> Result<(), UnsupportedFeature> {
+        if *self >= since {
+            return Ok(());
+        }
+        Err(UnsupportedFeature {
+            feature: feature.to_string(),
+            version: *self,
+            reason: format!("requires Druid {since}"),
+        })
+    }
+}
+
+// For clusters that leave druid.javascript.enabled unset. JavaScript filters, aggregators,
+// post-aggregators and extraction functions are all tagged "javascript", wherever they're nested.
+pub(crate) fn reject_javascript(
+    query: &impl Serialize,
+    version: &DruidVersion,
+) -> Result<(), UnsupportedFeature> {
+    fn javascript(value: &Value) -> bool {
+        match value {
+            Value::Object(object) => {
+                object.get("type").and_then(Value::as_str) == Some("javascript")
+                    || object.values().any(javascript)
+            }
+            Value::Array(values) => values.iter().any(javascript),
+            _ => false,
+        }
+    }
+    if *version < DruidVersion::JAVASCRIPT_DISABLED
+        || !serde_json::to_value(query).is_ok_and(|query| javascript(&query))
+    {
+        return Ok(());
+    }
+    Err(UnsupportedFeature {
+        feature: "JavaScript".to_string(),
+        version: *version,
+        reason: "disabled unless the cluster sets druid.javascript.enabled".to_string(),
+    })
+}
+
+impl Display for DruidVersion {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+impl FromStr for DruidVersion {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let release = s.trim().split(['-', '+']).next().unwrap_or_default();
+        let mut parts = release.split('.').map(|part| {
+            part.parse::<u32>()
+                .map_err(|_| format!("invalid Druid version {s:?}"))
+        });
+        let major = parts
+            .next()
+            .ok_or_else(|| format!("invalid Druid version {s:?}"))??;
+        let minor = parts.next().transpose()?.unwrap_or(0);
+        let patch = parts.next().transpose()?.unwrap_or(0);
+        Ok(Self::new(major, minor, patch))
+    }
+}
+
+impl Serialize for DruidVersion {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for DruidVersion {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+// A component the target version can't run and that has no equivalent it could be rewritten to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnsupportedFeature {
+    pub feature: String,
+    pub version: DruidVersion,
+    pub reason: String,
+}
+
+impl Display for UnsupportedFeature {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} is not available in Druid {}: {}",
+            self.feature, self.version, self.reason
+        )
+    }
+}
+
+impl std::error::Error for UnsupportedFeature {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::query::{NativeQuery, TypeConstrainedQuery};
+    use serde_json::{Value, json};
+
+    fn adapted(query: Value, version: &str) -> Result<Value, UnsupportedFeature> {
+        let mut query: NativeQuery = serde_json::from_value(query).unwrap();
+        query.adapt(&version.parse().unwrap())?;
+        Ok(serde_json::to_value(&query).unwrap())
+    }
+
+    #[test]
+    fn test_version_parsing() {
+        let version: DruidVersion = "0.22.1".parse().unwrap();
+        assert_eq!(version, DruidVersion::new(0, 22, 1));
+        assert_eq!(
+            "31.0.0-SNAPSHOT".parse::<DruidVersion>().unwrap(),
+            DruidVersion::new(31, 0, 0)
+        );
+        assert_eq!(
+            "28.0".parse::<DruidVersion>().unwrap(),
+            DruidVersion::new(28, 0, 0)
+        );
+        assert!("latest".parse::<DruidVersion>().is_err());
+        assert!(version < DruidVersion::TYPED_FILTERS);
+        assert_eq!(serde_json::to_string(&version).unwrap(), "\"0.22.1\"");
+    }
+
+    #[test]
+    fn test_typed_filters() {
+        let query = json!({
+            "queryType": "timeBoundary",
+            "dataSource": "wikipedia",
+            "filter": {"type": "and", "fields": [
+                {"type": "equals", "column": "channel", "matchValueType": "STRING", "matchValue": "#en.wikipedia"},
+                {"type": "not", "field": {"type": "null", "column": "user"}},
+                {"type": "range", "column": "added", "matchValueType": "LONG", "lower": 10, "lowerOpen": true}
+            ]}
+        });
+
+        let current = adapted(query.clone(), "31.0.0").unwrap();
+        assert_eq!(current["filter"]["fields"][0]["type"], "equals");
+
+        let filter = adapted(query, "0.22.1").unwrap()["filter"].take();
+        assert_eq!(
+            filter["fields"][0],
+            json!({"type": "selector", "dimension": "channel", "value": "#en.wikipedia"})
+        );
+        assert_eq!(
+            filter["fields"][1]["field"],
+            json!({"type": "selector", "dimension": "user", "value": null})
+        );
+        assert_eq!(filter["fields"][2]["type"], "bound");
+        assert_eq!(filter["fields"][2]["lower"], "10");
+        assert_eq!(filter["fields"][2]["lowerStrict"], true);
+        assert_eq!(filter["fields"][2]["ordering"], "numeric");
+
+        let array = json!({
+            "queryType": "timeBoundary",
+            "dataSource": "wikipedia",
+            "filter": {"type": "equals", "column": "tags", "matchValueType": "ARRAY<STRING>", "matchValue": ["a", "b"]}
+        });
+        assert!(adapted(array, "27.0.0").is_err());
+    }
+
+    #[test]
+    fn test_rejected_components() {
+        let unnest = json!({
+            "queryType": "scan",
+            "dataSource": {
+                "type": "unnest",
+                "base": {"type": "table", "name": "wikipedia"},
+                "virtualColumn": {"type": "expression", "name": "tag", "expression": "\"tags\"", "outputType": "STRING"},
+                "unnestFilter": null
+            },
+            "intervals": ["2015-09-12/2015-09-13"]
+        });
+        assert!(adapted(unnest.clone(), "27.0.0").is_ok());
+        let error = adapted(unnest, "26.0.0").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "unnest data source is not available in Druid 26.0.0: requires Druid 27.0.0"
+        );
+
+        let v1 = json!({
+            "queryType": "dataSourceMetadata",
+            "dataSource": "wikipedia",
+            "context": {"groupByStrategy": "v1"}
+        });
+        assert!(adapted(v1.clone(), "0.22.1").is_ok());
+        assert!(adapted(v1, "28.0.0").is_err());
+
+        let nested = json!({
+            "queryType": "scan",
+            "dataSource": "wikipedia",
+            "intervals": ["2015-09-12/2015-09-13"],
+            "virtualColumns": [
+                {"type": "nested-field", "columnName": "attrs", "outputName": "os", "path": "$.os"}
+            ]
+        });
+        assert!(adapted(nested.clone(), "24.0.0").is_ok());
+        let error = adapted(nested, "0.22.1").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "nested-field virtual column is not available in Druid 0.22.1: requires Druid 24.0.0"
+        );
+        let mv_filtered = json!({
+            "queryType": "timeseries",
+            "dataSource": "wikipedia",
+            "intervals": ["2015-09-12/2015-09-13"],
+            "granularity": "all",
+            "virtualColumns": [
+                {"type": "mv-filtered", "name": "v0", "delegate": "tags", "values": ["a"]}
+            ]
+        });
+        assert!(adapted(mv_filtered.clone(), "0.22.1").is_ok());
+        assert!(adapted(mv_filtered, "0.20.2").is_err());
+    }
+}
@@ -0,0 +1,455 @@
+use crate::client::{CathbadClientConfig, DruidError};
+use reqwest::StatusCode;
+use serde_json::{Value, json};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FakeRoute {
+    // POST /druid/v2
+    Native,
+    // POST /druid/v2/sql
+    Sql,
+    // GET /status and everything below it
+    Status,
+    // GET /druid/v2/datasources and everything below it
+    Datasources,
+    // DELETE /druid/v2/{queryId}
+    Cancel,
+}
+
+#[derive(Debug, Clone)]
+pub struct FakeResponse {
+    pub status: StatusCode,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+    // Before anything is written
+    pub delay: Duration,
+    // Chunk size and pause between chunks, sent with Transfer-Encoding: chunked
+    pub chunks: Option<(usize, Duration)>,
+}
+
+impl FakeResponse {
+    pub fn new(status: StatusCode, body: impl Into<String>) -> Self {
+        Self {
+            status,
+            headers: vec![],
+            body: body.into(),
+            delay: Duration::ZERO,
+            chunks: None,
+        }
+    }
+
+    pub fn json(value: Value) -> Self {
+        Self::new(StatusCode::OK, value.to_string())
+    }
+
+    // The legacy error body a broker sends for the error, with its matching status
+    pub fn error(druid_error: DruidError) -> Self {
+        let body = json!({
+            "error": druid_error.to_string(),
+            "errorMessage": format!("{druid_error} (scripted)"),
+            "errorClass": "org.apache.druid.query.QueryException",
+            "host": "fake-druid:8083",
+        });
+        Self::new(druid_error.status(), body.to_string())
+    }
+
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn with_delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    pub fn chunked(mut self, chunk_size: usize, interval: Duration) -> Self {
+        self.chunks = Some((chunk_size.max(1), interval));
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FakeRequest {
+    pub method: String,
+    pub path: String,
+    // Names are lowercased
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl FakeRequest {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        let name = name.to_ascii_lowercase();
+        self.headers
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn json(&self) -> Result<Value, serde_json::Error> {
+        serde_json::from_str(&self.body)
+    }
+}
+
+#[derive(Debug, Default)]
+struct FakeState {
+    scripted: HashMap<FakeRoute, VecDeque<FakeResponse>>,
+    fallback: HashMap<FakeRoute, FakeResponse>,
+    requests: Vec<FakeRequest>,
+}
+
+impl FakeState {
+    fn next_response(&mut self, route: FakeRoute, path: &str) -> FakeResponse {
+        if let Some(response) = self.scripted.get_mut(&route).and_then(VecDeque::pop_front) {
+            return response;
+        }
+        if let Some(response) = self.fallback.get(&route) {
+            return response.clone();
+        }
+        match route {
+            FakeRoute::Native | FakeRoute::Sql => FakeResponse::json(json!([])),
+            FakeRoute::Status if path.starts_with("/status/health") => {
+                FakeResponse::json(json!(true))
+            }
+            FakeRoute::Status => FakeResponse::json(json!({
+                "version": "30.0.0",
+                "modules": [],
+                "memory": {},
+            })),
+            FakeRoute::Datasources if path.trim_end_matches('/') == "/druid/v2/datasources" => {
+                FakeResponse::json(json!([]))
+            }
+            FakeRoute::Datasources if path.contains("/candidates") => FakeResponse::json(json!([])),
+            FakeRoute::Datasources => FakeResponse::json(json!({"dimensions": [], "metrics": []})),
+            FakeRoute::Cancel => FakeResponse::new(StatusCode::ACCEPTED, ""),
+        }
+    }
+}
+
+// Stand-in for a Druid broker on an ephemeral local port. Responses are scripted per route and
+// served in order, falling back to a fixed response and then to an empty success. Every request
+// is recorded. Connections are closed after each response.
+#[derive(Debug)]
+pub struct FakeDruid {
+    port: u16,
+    state: Arc<Mutex<FakeState>>,
+    server: JoinHandle<()>,
+}
+
+impl FakeDruid {
+    pub async fn start() -> std::io::Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let port = listener.local_addr()?.port();
+        let state = Arc::new(Mutex::new(FakeState::default()));
+
+        let server_state = state.clone();
+        let server = tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(handle(stream, server_state.clone()));
+            }
+        });
+        Ok(Self {
+            port,
+            state,
+            server,
+        })
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    pub fn url(&self) -> String {
+        format!("http://127.0.0.1:{}", self.port)
+    }
+
+    pub fn config(&self) -> CathbadClientConfig {
+        CathbadClientConfig {
+            druid_endpoint: self.url(),
+            ..Default::default()
+        }
+    }
+
+    // Served once, after the responses scripted before it
+    pub fn respond(&self, route: FakeRoute, response: FakeResponse) -> &Self {
+        self.state
+            .lock()
+            .unwrap()
+            .scripted
+            .entry(route)
+            .or_default()
+            .push_back(response);
+        self
+    }
+
+    // Served whenever nothing is scripted for the route
+    pub fn respond_always(&self, route: FakeRoute, response: FakeResponse) -> &Self {
+        self.state.lock().unwrap().fallback.insert(route, response);
+        self
+    }
+
+    pub fn requests(&self) -> Vec<FakeRequest> {
+        self.state.lock().unwrap().requests.clone()
+    }
+}
+
+impl Drop for FakeDruid {
+    fn drop(&mut self) {
+        self.server.abort();
+    }
+}
+
+fn route(request: &FakeRequest) -> Option<FakeRoute> {
+    let path = request.path.split('?').next().unwrap_or_default();
+    let path = path.trim_end_matches('/');
+    match (request.method.as_str(), path) {
+        ("POST", "/druid/v2") => Some(FakeRoute::Native),
+        ("POST", "/druid/v2/sql") => Some(FakeRoute::Sql),
+        ("GET", path) if path == "/status" || path.starts_with("/status/") => {
+            Some(FakeRoute::Status)
+        }
+        ("GET", path)
+            if path == "/druid/v2/datasources" || path.starts_with("/druid/v2/datasources/") =>
+        {
+            Some(FakeRoute::Datasources)
+        }
+        ("DELETE", path) => path
+            .strip_prefix("/druid/v2/")
+            .filter(|id| !id.is_empty() && !id.contains('/'))
+            .map(|_| FakeRoute::Cancel),
+        _ => None,
+    }
+}
+
+async fn handle(mut stream: TcpStream, state: Arc<Mutex<FakeState>>) {
+    let Ok(Some(request)) = read_request(&mut stream).await else {
+        return;
+    };
+    let response = {
+        let mut state = state.lock().unwrap();
+        let response = match route(&request) {
+            Some(route) => state.next_response(route, &request.path),
+            None => FakeResponse::new(StatusCode::NOT_FOUND, ""),
+        };
+        state.requests.push(request);
+        response
+    };
+    let _ = write_response(&mut stream, response).await;
+}
+
+async fn read_request(stream: &mut TcpStream) -> std::io::Result<Option<FakeRequest>> {
+    let mut buffer = Vec::new();
+    let mut chunk = [0; 4096];
+    let header_end = loop {
+        if let Some(position) = buffer.windows(4).position(|w| w == b"\r\n\r\n") {
+            break position;
+        }
+        let read = stream.read(&mut chunk).await?;
+        if read == 0 {
+            return Ok(None);
+        }
+        buffer.extend_from_slice(&chunk[..read]);
+    };
+
+    let head = String::from_utf8_lossy(&buffer[..header_end]).to_string();
+    let mut lines = head.split("\r\n");
+    let mut request_line = lines.next().unwrap_or_default().split(' ');
+    let method = request_line.next().unwrap_or_default().to_string();
+    let path = request_line.next().unwrap_or_default().to_string();
+    let headers: Vec<(String, String)> = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_ascii_lowercase(), value.trim().to_string()))
+        .collect();
+
+    let content_length = headers
+        .iter()
+        .find(|(name, _)| name == "content-length")
+        .and_then(|(_, value)| value.parse().ok())
+        .unwrap_or(0);
+    let mut body = buffer.split_off(header_end + 4);
+    while body.len() < content_length {
+        let read = stream.read(&mut chunk).await?;
+        if read == 0 {
+            break;
+        }
+        body.extend_from_slice(&chunk[..read]);
+    }
+
+    Ok(Some(FakeRequest {
+        method,
+        path,
+        headers,
+        body: String::from_utf8_lossy(&body).to_string(),
+    }))
+}
+
+async fn write_response(stream: &mut TcpStream, response: FakeResponse) -> std::io::Result<()> {
+    tokio::time::sleep(response.delay).await;
+
+    let mut head = format!(
+        "HTTP/1.1 {} {}\r\nConnection: close\r\n",
+        response.status.as_u16(),
+        response.status.canonical_reason().unwrap_or_default()
+    );
+    if !response
+        .headers
+        .iter()
+        .any(|(name, _)| name.eq_ignore_ascii_case("content-type"))
+    {
+        head.push_str("Content-Type: application/json\r\n");
+    }
+    for (name, value) in &response.headers {
+        head.push_str(&format!("{name}: {value}\r\n"));
+    }
+
+    match response.chunks {
+        Some((chunk_size, interval)) => {
+            head.push_str("Transfer-Encoding: chunked\r\n\r\n");
+            stream.write_all(head.as_bytes()).await?;
+            for chunk in response.body.as_bytes().chunks(chunk_size) {
+                stream
+                    .write_all(format!("{:x}\r\n", chunk.len()).as_bytes())
+                    .await?;
+                stream.write_all(chunk).await?;
+                stream.write_all(b"\r\n").await?;
+                stream.flush().await?;
+                tokio::time::sleep(interval).await;
+            }
+            stream.write_all(b"0\r\n\r\n").await?;
+        }
+        None => {
+            head.push_str(&format!("Content-Length: {}\r\n\r\n", response.body.len()));
+            stream.write_all(head.as_bytes()).await?;
+            stream.write_all(response.body.as_bytes()).await?;
+        }
+    }
+    stream.shutdown().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::{CathbadClient, CathbadClientError, DruidClient, RetryPolicy};
+    use crate::query::{SqlQuery, SqlResponse, SqlResultFormat};
+
+    fn retrying(fake: &FakeDruid) -> CathbadClient {
+        CathbadClient::new(fake.config())
+            .unwrap()
+            .with_retry_policy(RetryPolicy {
+                initial_backoff: Duration::from_millis(1),
+                jitter: false,
+                ..Default::default()
+            })
+    }
+
+    #[tokio::test]
+    async fn test_error_statuses() {
+        let fake = FakeDruid::start().await.unwrap();
+        let client = CathbadClient::new(fake.config()).unwrap();
+
+        for druid_error in [
+            DruidError::SQLParseFailed,
+            DruidError::QueryCapacityExceeded,
+            DruidError::QueryInterrupted,
+            DruidError::UnsupportedOperation,
+            DruidError::QueryTimeout,
+        ] {
+            fake.respond(FakeRoute::Sql, FakeResponse::error(druid_error.clone()));
+            let error = client.sql(SqlQuery::new("SELECT 1")).await.unwrap_err();
+            assert_eq!(error.status(), Some(druid_error.status()));
+            assert!(matches!(
+                error,
+                CathbadClientError::Druid { druid_error: ref e, .. } if *e == druid_error
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retries_until_success() {
+        let fake = FakeDruid::start().await.unwrap();
+        fake.respond(
+            FakeRoute::Sql,
+            FakeResponse::error(DruidError::QueryCapacityExceeded).with_header("Retry-After", "0"),
+        )
+        .respond(
+            FakeRoute::Sql,
+            FakeResponse::new(StatusCode::BAD_GATEWAY, "<html>Bad Gateway</html>"),
+        )
+        .respond(FakeRoute::Sql, FakeResponse::json(json!([{"EXPR$0": 1}])));
+
+        let response = retrying(&fake)
+            .sql(SqlQuery::new("SELECT 1"))
+            .await
+            .unwrap();
+        assert_eq!(
+            response,
+            SqlResponse::Objects(vec![json!({"EXPR$0": 1}).as_object().unwrap().clone()])
+        );
+
+        let requests = fake.requests();
+        assert_eq!(requests.len(), 3);
+        assert!(requests.iter().all(|r| r.method == "POST"));
+        assert_eq!(requests[2].json().unwrap()["query"], "SELECT 1");
+
+        fake.respond_always(
+            FakeRoute::Sql,
+            FakeResponse::error(DruidError::QueryTimeout),
+        );
+        let error = retrying(&fake)
+            .sql(SqlQuery::new("SELECT 1"))
+            .await
+            .unwrap_err();
+        assert!(error.is_retryable());
+        assert_eq!(fake.requests().len(), 6);
+    }
+
+    #[tokio::test]
+    async fn test_slow_chunked_response() {
+        let fake = FakeDruid::start().await.unwrap();
+        fake.respond(
+            FakeRoute::Sql,
+            FakeResponse::new(StatusCode::OK, "[\"Main\",3]\n[\"Talk\",1]\n\n")
+                .with_delay(Duration::from_millis(20))
+                .chunked(4, Duration::from_millis(5)),
+        );
+
+        let mut query = SqlQuery::new("SELECT page, COUNT(*) FROM wikipedia GROUP BY 1");
+        query.result_format = Some(SqlResultFormat::ArrayLines);
+        let client = CathbadClient::new(fake.config()).unwrap();
+        let response = client.sql(query).await.unwrap();
+        assert_eq!(
+            response,
+            SqlResponse::Arrays(vec![
+                vec![json!("Main"), json!(3)],
+                vec![json!("Talk"), json!(1)]
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_status_and_cancel() {
+        let fake = FakeDruid::start().await.unwrap();
+        let health = reqwest::get(format!("{}/status/health", fake.url()))
+            .await
+            .unwrap();
+        assert_eq!(health.text().await.unwrap(), "true");
+        let missing = reqwest::get(format!("{}/druid/v3", fake.url()))
+            .await
+            .unwrap();
+        assert_eq!(missing.status(), StatusCode::NOT_FOUND);
+
+        let client = CathbadClient::new(fake.config()).unwrap();
+        client.cancel("dashboard-42").await.unwrap();
+        let requests = fake.requests();
+        assert_eq!(requests[2].method, "DELETE");
+        assert_eq!(requests[2].path, "/druid/v2/dashboard-42");
+        assert_eq!(requests[2].header("Accept"), Some("application/json"));
+    }
+}